@@ -1,22 +1,227 @@
 use crate::error::*;
-use std::io::{Read, BufWriter};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Seek};
 use std::io;
 use std::ops;
+use std::path::Path;
 use std::process::ChildStdin;
 use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+/// Compression format of the `control.tar` and `data.tar` archive members
+#[derive(Debug, Copy, Clone, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[serde(alias = "gzip")]
+    Gz,
+    Xz,
+    #[serde(alias = "zstd")]
+    Zst,
+    /// Plain `.tar`, mostly useful for quick test builds
+    #[serde(rename = "none")]
+    Uncompressed,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        // Very old dpkg doesn't support LZMA, so without the lzma feature stick to gzip
+        if cfg!(feature = "lzma") { Self::Xz } else { Self::Gz }
+    }
+}
+
+impl FromStr for Format {
+    type Err = CargoDebError;
+
+    fn from_str(s: &str) -> CDResult<Self> {
+        Ok(match s {
+            "gz" | "gzip" => Self::Gz,
+            "xz" => Self::Xz,
+            "zst" | "zstd" => Self::Zst,
+            "none" => Self::Uncompressed,
+            _ => return Err(CargoDebError::Str("unknown compression type, expected one of: gz, xz, zst, none")),
+        })
+    }
+}
+
+/// Compression of DWARF sections in separated debug files,
+/// like `objcopy --compress-debug-sections=…`
+#[derive(Debug, Copy, Clone, Eq, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DebugSectionsCompression {
+    Zlib,
+    #[serde(alias = "zst")]
+    Zstd,
+}
+
+impl FromStr for DebugSectionsCompression {
+    type Err = CargoDebError;
+
+    fn from_str(s: &str) -> CDResult<Self> {
+        Ok(match s {
+            "zlib" | "gz" | "gzip" => Self::Zlib,
+            "zstd" | "zst" => Self::Zstd,
+            _ => return Err(CargoDebError::Str("unknown debug sections compression, expected zlib or zstd")),
+        })
+    }
+}
+
+impl fmt::Display for DebugSectionsCompression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Zlib => "zlib",
+            Self::Zstd => "zstd",
+        })
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extension().unwrap_or("none"))
+    }
+}
+
+impl Format {
+    /// `None` for uncompressed tarballs
+    #[must_use]
+    pub fn extension(self) -> Option<&'static str> {
+        match self {
+            Self::Gz => Some("gz"),
+            Self::Xz => Some("xz"),
+            Self::Zst => Some("zst"),
+            Self::Uncompressed => None,
+        }
+    }
+
+    /// Name of the ar member, e.g. `data.tar.xz` for `data`
+    #[must_use]
+    pub fn tar_file_name(self, stem: &str) -> String {
+        match self.extension() {
+            Some(ext) => format!("{stem}.tar.{ext}"),
+            None => format!("{stem}.tar"),
+        }
+    }
+
+    /// Parses the ar member name, e.g. `data.tar.xz` for `data`
+    #[must_use]
+    pub fn from_tar_file_name(name: &str, stem: &str) -> Option<Self> {
+        match name.strip_prefix(stem)?.strip_prefix(".tar")? {
+            "" => Some(Self::Uncompressed),
+            ".gz" => Some(Self::Gz),
+            ".xz" => Some(Self::Xz),
+            ".zst" => Some(Self::Zst),
+            _ => None,
+        }
+    }
+
+    /// Levels accepted by the encoder, and the (fast, default) levels
+    fn levels(self) -> (ops::RangeInclusive<u32>, u32, u32) {
+        match self {
+            // gzip command doesn't accept -0
+            Self::Gz => (1..=9, 1, 9),
+            // Compression level 6 is a good trade off between size and [ridiculously] long compression time
+            Self::Xz => (0..=9, 1, 6),
+            // Level 19 is what Ubuntu uses for its own packages
+            Self::Zst => (1..=22, 1, 19),
+            Self::Uncompressed => (0..=0, 0, 0),
+        }
+    }
+}
+
+/// Compression level, optionally with xz's "extreme" flag (`9e`)
+#[derive(Debug, Copy, Clone, Eq, PartialEq, serde::Deserialize)]
+#[serde(try_from = "LevelSetting")]
+pub struct Level {
+    pub level: u32,
+    pub extreme: bool,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum LevelSetting {
+    Num(u32),
+    Str(String),
+}
+
+impl TryFrom<LevelSetting> for Level {
+    type Error = CargoDebError;
+
+    fn try_from(s: LevelSetting) -> CDResult<Self> {
+        match s {
+            LevelSetting::Num(level) => Ok(Self { level, extreme: false }),
+            LevelSetting::Str(s) => s.parse(),
+        }
+    }
+}
+
+impl FromStr for Level {
+    type Err = CargoDebError;
+
+    fn from_str(s: &str) -> CDResult<Self> {
+        let (num, extreme) = match s.strip_suffix('e') {
+            Some(num) => (num, true),
+            None => (s, false),
+        };
+        Ok(Self {
+            level: num.parse().map_err(|e| CargoDebError::NumParse("invalid compression level", e))?,
+            extreme,
+        })
+    }
+}
+
+/// How to compress `control.tar` and `data.tar`
+#[derive(Debug, Copy, Clone, Default)]
+pub struct CompressConfig {
+    pub format: Format,
+    /// Format-specific level. Defaults depend on `fast`.
+    pub level: Option<Level>,
+    /// Number of encoder threads for xz and zstd. Defaults to the number of CPUs.
+    pub threads: Option<u32>,
+    /// Prefer speed over size when `level` is not set
+    pub fast: bool,
+    /// Pipe data through a command-line tool instead of the built-in encoder
+    pub use_system: bool,
+}
+
+impl CompressConfig {
+    /// Applies the format's defaults and checks that the level makes sense for the format
+    fn level(&self) -> CDResult<Level> {
+        let (range, fast, default) = self.format.levels();
+        let level = self.level.unwrap_or(Level { level: if self.fast { fast } else { default }, extreme: false });
+        if !range.contains(&level.level) {
+            return Err(CargoDebError::InvalidCompressionLevel(level.level, self.format));
+        }
+        if level.extreme && self.format != Format::Xz {
+            return Err(CargoDebError::Str("the extreme (`e`) compression level is only supported by xz"));
+        }
+        Ok(level)
+    }
+
+    fn threads(&self) -> u32 {
+        self.threads.unwrap_or_else(|| {
+            std::thread::available_parallelism().map_or(1, |n| n.get() as u32)
+        })
+    }
+}
+
+type TempFileWriter = BufWriter<File>;
 
 enum Writer {
     #[cfg(feature = "lzma")]
-    Xz(xz2::write::XzEncoder<Vec<u8>>),
+    Xz(xz2::write::XzEncoder<TempFileWriter>),
+    #[cfg(feature = "zstd")]
+    Zst(zstd::Encoder<'static, TempFileWriter>),
+    Gz(flate2::write::GzEncoder<TempFileWriter>),
+    Plain(TempFileWriter),
     StdIn(BufWriter<ChildStdin>),
-    #[cfg(not(feature = "lzma"))]
-    Gz(flate2::write::GzEncoder<Vec<u8>>),
 }
 
+/// Compresses everything written to it into an anonymous temporary file,
+/// so that the package contents never have to fit in memory.
 pub struct Compressor {
     writer: Writer,
     ret: Box<dyn FnOnce(Writer) -> io::Result<Compressed> + Send + Sync>,
-    pub uncompressed_size: usize,
+    pub uncompressed_size: u64,
 }
 
 impl io::Write for Compressor {
@@ -24,8 +229,10 @@ impl io::Write for Compressor {
         match &mut self.writer {
             #[cfg(feature = "lzma")]
             Writer::Xz(w) => w.flush(),
-            #[cfg(not(feature = "lzma"))]
+            #[cfg(feature = "zstd")]
+            Writer::Zst(w) => w.flush(),
             Writer::Gz(w) => w.flush(),
+            Writer::Plain(w) => w.flush(),
             Writer::StdIn(w) => w.flush(),
         }
     }
@@ -34,11 +241,13 @@ impl io::Write for Compressor {
         let len = match &mut self.writer {
             #[cfg(feature = "lzma")]
             Writer::Xz(w) => w.write(buf),
-            #[cfg(not(feature = "lzma"))]
+            #[cfg(feature = "zstd")]
+            Writer::Zst(w) => w.write(buf),
             Writer::Gz(w) => w.write(buf),
+            Writer::Plain(w) => w.write(buf),
             Writer::StdIn(w) => w.write(buf),
         }?;
-        self.uncompressed_size += len;
+        self.uncompressed_size += len as u64;
         Ok(len)
     }
 
@@ -46,11 +255,13 @@ impl io::Write for Compressor {
         match &mut self.writer {
             #[cfg(feature = "lzma")]
             Writer::Xz(w) => w.write_all(buf),
-            #[cfg(not(feature = "lzma"))]
+            #[cfg(feature = "zstd")]
+            Writer::Zst(w) => w.write_all(buf),
             Writer::Gz(w) => w.write_all(buf),
+            Writer::Plain(w) => w.write_all(buf),
             Writer::StdIn(w) => w.write_all(buf),
         }?;
-        self.uncompressed_size += buf.len();
+        self.uncompressed_size += buf.len() as u64;
         Ok(())
     }
 }
@@ -69,69 +280,125 @@ impl Compressor {
     }
 }
 
-pub enum Compressed {
-    Gz(Vec<u8>),
-    Xz(Vec<u8>),
+/// Compressed archive stored in a temporary file, ready to be read from the start
+pub struct Compressed {
+    format: Format,
+    file: File,
+    size: u64,
 }
 
-impl ops::Deref for Compressed {
-    type Target = Vec<u8>;
-
-    fn deref(&self) -> &Self::Target {
-        match self {
-            Self::Gz(data) | Self::Xz(data) => data,
-        }
+impl io::Read for Compressed {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buf)
     }
 }
 
 impl Compressed {
-    pub fn extension(&self) -> &'static str {
-        match self {
-            Self::Gz(_) => "gz",
-            Self::Xz(_) => "xz",
-        }
+    #[must_use]
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Length of the compressed data in bytes
+    #[must_use]
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn new(format: Format, mut file: File) -> io::Result<Self> {
+        let size = file.stream_position()?;
+        file.rewind()?;
+        Ok(Self { format, file, size })
+    }
+
+    fn from_writer(format: Format, writer: TempFileWriter) -> io::Result<Self> {
+        Self::new(format, writer.into_inner().map_err(|e| e.into_error())?)
     }
 }
 
-fn system_xz(fast: bool) -> CDResult<Compressor> {
-    let mut child = Command::new("xz")
-        .arg(if fast { "-1" } else { "-6" })
+/// Pipes the data through `xz`, `gzip` or `zstd` command
+fn system_compressor(config: &CompressConfig, temp_dir: &Path) -> CDResult<Compressor> {
+    let format = config.format;
+    let cmd_name = match format {
+        Format::Gz => "gzip",
+        Format::Xz => "xz",
+        Format::Zst => "zstd",
+        Format::Uncompressed => return uncompressed(temp_dir),
+    };
+    let mut file = tempfile::tempfile_in(temp_dir)?;
+    let level = config.level()?;
+    let mut cmd = Command::new(cmd_name);
+    cmd.arg(format!("-{}{}", level.level, if level.extreme { "e" } else { "" }));
+    if level.level > 19 && format == Format::Zst {
+        cmd.arg("--ultra");
+    }
+    if format != Format::Gz {
+        cmd.arg(format!("-T{}", config.threads()));
+    }
+    let mut child = cmd
+        .arg("-q")
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::inherit())
         .spawn()
-        .map_err(|e| CargoDebError::CommandFailed(e, "xz"))?;
+        .map_err(|e| CargoDebError::CommandFailed(e, cmd_name))?;
     let mut stdout = child.stdout.take().unwrap();
 
     let t = std::thread::spawn(move || {
-        let mut buf = Vec::new();
-        stdout.read_to_end(&mut buf).map(|_| buf)
+        io::copy(&mut stdout, &mut file).map(|_| file)
     });
 
     let stdin = BufWriter::with_capacity(1<<16, child.stdin.take().unwrap());
     Ok(Compressor::new(Writer::StdIn(stdin), move |stdin| {
         drop(stdin);
         child.wait()?;
-        t.join().unwrap().map(Compressed::Xz)
+        Compressed::new(format, t.join().unwrap()?)
     }))
 }
 
-/// Compresses data using the [native Rust implementation of Zopfli](https://github.com/carols10cents/zopfli).
-#[cfg(not(feature = "lzma"))]
-pub fn xz_or_gz(fast: bool, with_system_xz: bool) -> CDResult<Compressor> {
-    // Very old dpkg doesn't support LZMA, so use it only if expliclty enabled
-    if with_system_xz {
-        return system_xz(fast);
+/// Picks a compressor for the configured format. The output is kept in a temporary file in `temp_dir`.
+///
+/// Formats that have not been compiled in (see `lzma` and `zstd` Cargo features)
+/// fall back to the command-line tool, as does `use_system`.
+pub fn select_compressor(config: &CompressConfig, temp_dir: &Path) -> CDResult<Compressor> {
+    if config.use_system {
+        return system_compressor(config, temp_dir);
     }
+    match config.format {
+        Format::Gz => gz(config, temp_dir),
+        #[cfg(feature = "lzma")]
+        Format::Xz => xz(config, temp_dir),
+        #[cfg(feature = "zstd")]
+        Format::Zst => zst(config, temp_dir),
+        Format::Uncompressed => uncompressed(temp_dir),
+        #[allow(unreachable_patterns)]
+        _ => system_compressor(config, temp_dir),
+    }
+}
 
+fn temp_file_writer(temp_dir: &Path) -> io::Result<TempFileWriter> {
+    Ok(BufWriter::with_capacity(1<<16, tempfile::tempfile_in(temp_dir)?))
+}
+
+fn uncompressed(temp_dir: &Path) -> CDResult<Compressor> {
+    Ok(Compressor::new(Writer::Plain(temp_file_writer(temp_dir)?), |writer| {
+        match writer {
+            Writer::Plain(w) => Compressed::from_writer(Format::Uncompressed, w),
+            _ => unreachable!(),
+        }
+    }))
+}
+
+/// Compresses data using the flate2 library
+fn gz(config: &CompressConfig, temp_dir: &Path) -> CDResult<Compressor> {
     use flate2::Compression;
     use flate2::write::GzEncoder;
 
-    let writer = GzEncoder::new(Vec::new(), if fast { Compression::fast() } else { Compression::best() });
+    let writer = GzEncoder::new(temp_file_writer(temp_dir)?, Compression::new(config.level()?.level));
 
     Ok(Compressor::new(Writer::Gz(writer), move |writer| {
         match writer {
-            Writer::Gz(w) => Ok(Compressed::Gz(w.finish()?)),
+            Writer::Gz(w) => Compressed::from_writer(Format::Gz, w.finish()?),
             _ => unreachable!(),
         }
     }))
@@ -139,24 +406,114 @@ pub fn xz_or_gz(fast: bool, with_system_xz: bool) -> CDResult<Compressor> {
 
 /// Compresses data using the xz2 library
 #[cfg(feature = "lzma")]
-pub fn xz_or_gz(fast: bool, with_system_xz: bool) -> CDResult<Compressor> {
-    if with_system_xz {
-        return system_xz(fast);
-    }
-
-    // Compression level 6 is a good trade off between size and [ridiculously] long compression time
+fn xz(config: &CompressConfig, temp_dir: &Path) -> CDResult<Compressor> {
+    // LZMA_PRESET_EXTREME from liblzma, not re-exported by xz2
+    const PRESET_EXTREME: u32 = 1 << 31;
+    let level = config.level()?;
+    let preset = if level.extreme { level.level | PRESET_EXTREME } else { level.level };
     let encoder = xz2::stream::MtStreamBuilder::new()
-        .threads(num_cpus::get() as u32)
-        .preset(if fast { 1 } else { 6 })
+        .threads(config.threads())
+        .preset(preset)
         .encoder()
         .map_err(CargoDebError::LzmaCompressionError)?;
 
-    let writer = xz2::write::XzEncoder::new_stream(Vec::new(), encoder);
+    let writer = xz2::write::XzEncoder::new_stream(temp_file_writer(temp_dir)?, encoder);
 
     Ok(Compressor::new(Writer::Xz(writer), |writer| {
         match writer {
-            Writer::Xz(w) => w.finish().map(Compressed::Xz),
+            Writer::Xz(w) => Compressed::from_writer(Format::Xz, w.finish()?),
             _ => unreachable!(),
         }
     }))
 }
+
+/// Compresses data using the zstd library
+#[cfg(feature = "zstd")]
+fn zst(config: &CompressConfig, temp_dir: &Path) -> CDResult<Compressor> {
+    let mut writer = zstd::Encoder::new(temp_file_writer(temp_dir)?, config.level()?.level as i32)?;
+    // Always in multi-threaded mode, because its output doesn't depend on the number of threads,
+    // but is different from the single-threaded mode
+    writer.multithread(config.threads())?;
+
+    Ok(Compressor::new(Writer::Zst(writer), |writer| {
+        match writer {
+            Writer::Zst(w) => Compressed::from_writer(Format::Zst, w.finish()?),
+            _ => unreachable!(),
+        }
+    }))
+}
+
+/// Decompresses a `control.tar` or `data.tar` archive member while it's being read
+pub fn decompress<'a>(format: Format, reader: impl io::Read + 'a) -> CDResult<Box<dyn io::Read + 'a>> {
+    Ok(match format {
+        Format::Gz => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+        #[cfg(feature = "lzma")]
+        Format::Xz => Box::new(xz2::read::XzDecoder::new_multi_decoder(reader)),
+        #[cfg(feature = "zstd")]
+        Format::Zst => Box::new(zstd::Decoder::new(reader)?),
+        Format::Uncompressed => Box::new(reader),
+        #[allow(unreachable_patterns)]
+        _ => return Err(CargoDebError::Str("this compression format is not supported by this build of cargo-deb")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+
+    fn compress(format: Format) -> Vec<u8> {
+        let mut c = select_compressor(&CompressConfig { format, fast: true, ..Default::default() }, &std::env::temp_dir()).unwrap();
+        c.write_all(b"hello hello hello hello").unwrap();
+        assert_eq!(23, c.uncompressed_size);
+        let mut compressed = c.finish().unwrap();
+        assert_eq!(format, compressed.format());
+        let mut data = Vec::new();
+        compressed.read_to_end(&mut data).unwrap();
+        assert_eq!(data.len() as u64, compressed.size());
+        data
+    }
+
+    #[test]
+    fn gz_roundtrip() {
+        let compressed = compress(Format::Gz);
+        let mut out = String::new();
+        flate2::read::GzDecoder::new(&compressed[..]).read_to_string(&mut out).unwrap();
+        assert_eq!("hello hello hello hello", out);
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn zst_roundtrip() {
+        let compressed = compress(Format::Zst);
+        assert_eq!(b"hello hello hello hello"[..], zstd::decode_all(&compressed[..]).unwrap());
+    }
+
+    #[test]
+    fn uncompressed() {
+        let compressed = compress(Format::Uncompressed);
+        assert_eq!(b"hello hello hello hello"[..], compressed[..]);
+        assert_eq!("data.tar", Format::Uncompressed.tar_file_name("data"));
+    }
+
+    #[test]
+    fn levels() {
+        let c = CompressConfig { format: Format::Xz, level: Some("9e".parse().unwrap()), ..Default::default() };
+        assert_eq!(Level { level: 9, extreme: true }, c.level().unwrap());
+        let c = CompressConfig { format: Format::Zst, level: Some(Level { level: 9, extreme: true }), ..Default::default() };
+        assert!(c.level().is_err());
+        let c = CompressConfig { format: Format::Gz, level: Some(Level { level: 10, extreme: false }), ..Default::default() };
+        assert!(select_compressor(&c, &std::env::temp_dir()).is_err());
+        let c = CompressConfig { format: Format::Gz, level: Some(Level { level: 0, extreme: false }), use_system: true, ..Default::default() };
+        assert!(c.level().is_err());
+        let c = CompressConfig { format: Format::Zst, fast: true, ..Default::default() };
+        assert_eq!(1, c.level().unwrap().level);
+    }
+
+    #[test]
+    fn parse_format() {
+        assert_eq!(Format::Zst, "zstd".parse().unwrap());
+        assert_eq!(Format::Gz, "gz".parse().unwrap());
+        assert!("bz2".parse::<Format>().is_err());
+    }
+}
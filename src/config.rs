@@ -102,6 +102,13 @@ impl CargoConfig {
         &self.path
     }
 
+    /// `[target.<triple>] sysroot = "…"`, relative to the directory containing `.cargo/`
+    pub fn sysroot(&self, target_triple: &str) -> Option<PathBuf> {
+        let sysroot = self.target_conf(target_triple)?.get("sysroot")?.as_str()?;
+        let base = self.path.parent().and_then(|dot_cargo| dot_cargo.parent()).unwrap_or(Path::new("."));
+        Some(base.join(sysroot))
+    }
+
     fn linker_command(&self, target_triple: &str) -> Option<&Path> {
         if let Some(target) = self.target_conf(target_triple) {
             return target.get("linker").and_then(|l| l.as_str()).map(Path::new);
@@ -117,6 +124,32 @@ impl CargoConfig {
     }
 }
 
+/// Finds `strip`/`objcopy` of a cross toolchain that the sysroot is part of,
+/// e.g. `/opt/x-tools/bin/aarch64-linux-gnu-strip` for `/opt/x-tools/aarch64-linux-gnu/sysroot`.
+pub(crate) fn sysroot_toolchain_command(sysroot: &Path, target_triple: &str, command_name: &str) -> Option<PathBuf> {
+    let debian_target_triple = crate::debian_triple(target_triple);
+    let names = [format!("{debian_target_triple}-{command_name}"), format!("{target_triple}-{command_name}")];
+    sysroot.ancestors().skip(1).take(2)
+        .map(|dir| dir.join("bin"))
+        .flat_map(|bin| names.iter().map(move |name| bin.join(name)))
+        .find(|path| path.exists())
+}
+
+#[test]
+fn parse_sysroot() {
+    let c = CargoConfig::from_str(r#"
+[target.aarch64-unknown-linux-gnu]
+sysroot = "sysroots/arm64"
+
+[target.'foo']
+sysroot = "/abs"
+"#, "/project/.cargo/config.toml".into()).unwrap();
+
+    assert_eq!(Path::new("/project/sysroots/arm64"), c.sysroot("aarch64-unknown-linux-gnu").unwrap());
+    assert_eq!(Path::new("/abs"), c.sysroot("foo").unwrap());
+    assert_eq!(None, c.sysroot("bar"));
+}
+
 #[test]
 fn parse_strip() {
     let c = CargoConfig::from_str(r#"
@@ -1,8 +1,9 @@
+use crate::deb822::ControlParagraph;
 use crate::dh_installsystemd;
 use crate::dh_lib;
 use crate::error::*;
 use crate::listener::Listener;
-use crate::manifest::Config;
+use crate::manifest::{Asset, Config, Owner};
 use crate::pathbytes::*;
 use crate::tararchive::Archive;
 use crate::util::{is_path_file, read_file_to_bytes};
@@ -63,58 +64,100 @@ impl<'l, W: Write> ControlArchiveBuilder<'l, W> {
     /// contain a `#DEBHELPER#` token at the point where shell script fragments
     /// should be inserted.
     fn generate_scripts(&mut self, option: &Config) -> CDResult<()> {
-        if let Some(ref maintainer_scripts_dir) = option.maintainer_scripts {
-            let maintainer_scripts_dir = option.pacakge_manifest_dir.as_path().join(maintainer_scripts_dir);
-            let mut scripts;
-
-            if let Some(systemd_units_config) = &option.systemd_units {
-                // Select and populate autoscript templates relevant to the unit
-                // file(s) in this package and the configuration settings chosen.
-                scripts = dh_installsystemd::generate(
-                    &option.name,
-                    &option.assets.resolved,
-                    &dh_installsystemd::Options::from(systemd_units_config),
-                    self.listener,
-                )?;
-
-                // Get Option<&str> from Option<String>
-                let unit_name = systemd_units_config.unit_name.as_deref();
-
-                // Replace the #DEBHELPER# token in the users maintainer scripts
-                // and/or generate maintainer scripts from scratch as needed.
-                dh_lib::apply(
-                    &maintainer_scripts_dir,
-                    &mut scripts,
-                    &option.name,
-                    unit_name,
-                    self.listener)?;
-            } else {
-                scripts = ScriptFragments::with_capacity(0);
-            }
+        let maintainer_scripts_dir = option.maintainer_scripts.as_ref()
+            .map(|dir| option.pacakge_manifest_dir.as_path().join(dir));
+        let mut scripts = ScriptFragments::with_capacity(0);
+        let mut unit_name = None;
+
+        if let (Some(_), Some(systemd_units_config)) = (&maintainer_scripts_dir, &option.systemd_units) {
+            // Select and populate autoscript templates relevant to the unit
+            // file(s) in this package and the configuration settings chosen.
+            scripts = dh_installsystemd::generate(
+                &option.name,
+                &option.assets.resolved,
+                &dh_installsystemd::Options::from(systemd_units_config),
+                self.listener,
+            )?;
+
+            // Get Option<&str> from Option<String>
+            unit_name = systemd_units_config.unit_name.as_deref();
+        }
+
+        self.generate_chown_fragments(option, &mut scripts)?;
+
+        if (option.systemd_units.is_some() && maintainer_scripts_dir.is_some()) || !scripts.is_empty() {
+            // Replace the #DEBHELPER# token in the users maintainer scripts
+            // and/or generate maintainer scripts from scratch as needed.
+            dh_lib::apply(
+                maintainer_scripts_dir.as_deref(),
+                &mut scripts,
+                &option.name,
+                unit_name,
+                self.listener)?;
+        }
 
-            // Add maintainer scripts to the archive, either those supplied by the
-            // user or if available prefer modified versions generated above.
-            for name in &["config", "preinst", "postinst", "prerm", "postrm", "templates"] {
-                let mut script = scripts.remove(&name.to_string());
+        // Add maintainer scripts to the archive, either those supplied by the
+        // user or if available prefer modified versions generated above.
+        for name in &["config", "preinst", "postinst", "prerm", "postrm", "templates"] {
+            let mut script = scripts.remove(*name);
 
-                if script.is_none() {
+            if script.is_none() {
+                if let Some(maintainer_scripts_dir) = &maintainer_scripts_dir {
                     let script_path = maintainer_scripts_dir.join(name);
                     if is_path_file(&script_path) {
                         script = Some(read_file_to_bytes(&script_path)?);
                     }
                 }
+            }
 
-                if let Some(contents) = script {
-                    // The config, postinst, postrm, preinst, and prerm
-                    // control files should use mode 0755; all other control files should use 0644.
-                    // See Debian Policy Manual section 10.9
-                    // and lintian tag control-file-has-bad-permissions
-                    let permissions = if *name == "templates" { 0o644 } else { 0o755 };
-                    self.archive.file(name, &contents, permissions)?;
+            if let Some(contents) = script {
+                // The config, postinst, postrm, preinst, and prerm
+                // control files should use mode 0755; all other control files should use 0644.
+                // See Debian Policy Manual section 10.9
+                // and lintian tag control-file-has-bad-permissions
+                let permissions = if *name == "templates" { 0o644 } else { 0o755 };
+                self.archive.file(name, &contents, permissions)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Assets owned by named accounts are unpacked as root if the account doesn't exist yet
+    /// (e.g. it's created by this package's preinst), so postinst sets the ownership again.
+    fn generate_chown_fragments(&self, option: &Config, scripts: &mut ScriptFragments) -> CDResult<()> {
+        let mut by_owner: Vec<(&Owner, Vec<&Asset>)> = Vec::new();
+        for asset in &option.assets.resolved {
+            // chown would follow the symlink
+            if asset.source.archive_as_symlink_only() {
+                continue;
+            }
+            if let Some(owner) = asset.c.owner.as_ref().filter(|o| o.needs_chown()) {
+                match by_owner.iter_mut().find(|(o, _)| *o == owner) {
+                    Some((_, assets)) => assets.push(asset),
+                    None => by_owner.push((owner, vec![asset])),
                 }
             }
         }
 
+        for (owner, assets) in by_owner {
+            let paths = |assets: &mut dyn Iterator<Item = &&Asset>| assets
+                .map(|a| shell_quote(&Path::new("/").join(&a.c.target_path).to_string_lossy()))
+                .collect::<Vec<_>>().join(" ");
+            dh_lib::autoscript(scripts, &option.name, "postinst", "postinst-chown",
+                &HashMap::from([("OWNER", owner.to_string()), ("FILES", paths(&mut assets.iter()))]),
+                false, self.listener)?;
+
+            // chown clears the setuid and setgid bits
+            let mut special_modes: Vec<u32> = assets.iter().map(|a| a.c.chmod).filter(|m| m & 0o6000 != 0).collect();
+            special_modes.sort_unstable();
+            special_modes.dedup();
+            for mode in special_modes {
+                dh_lib::autoscript(scripts, &option.name, "postinst", "postinst-chmod",
+                    &HashMap::from([("MODE", format!("{mode:o}")), ("FILES", paths(&mut assets.iter().filter(|a| a.c.chmod == mode)))]),
+                    false, self.listener)?;
+            }
+        }
         Ok(())
     }
 
@@ -133,6 +176,17 @@ impl<'l, W: Write> ControlArchiveBuilder<'l, W> {
             }
         }
 
+        // Files generated while making the archive, like SBOMs
+        let mut extra_files: Vec<_> = asset_hashes.iter()
+            .filter(|(path, _)| !options.assets.resolved.iter().any(|asset| &asset.c.target_path == *path))
+            .collect();
+        extra_files.sort_by(|a, b| a.0.cmp(b.0));
+        for (path, value) in extra_files {
+            write!(md5sums, "{:x}  ", value)?;
+            md5sums.write_all(&path.as_path().as_unix_path())?;
+            md5sums.write_all(b"\n")?;
+        }
+
         // Write the data to the archive
         self.archive.file("./md5sums", &md5sums, 0o644)?;
         Ok(())
@@ -140,97 +194,77 @@ impl<'l, W: Write> ControlArchiveBuilder<'l, W> {
 
     /// Generates the control file that obtains all the important information about the package.
     fn generate_control(&mut self, options: &Config) -> CDResult<()> {
-        // Create and return the handle to the control file with write access.
-        let mut control: Vec<u8> = Vec::with_capacity(1024);
-
-        // Write all of the lines required by the control file.
-        writeln!(&mut control, "Package: {}", options.deb_name)?;
-        writeln!(&mut control, "Version: {}", options.deb_version)?;
-        writeln!(&mut control, "Architecture: {}", options.architecture)?;
+        let mut control = ControlParagraph::default();
+
+        control.set("Package", &*options.deb_name);
+        control.set("Version", &*options.deb_version);
+        control.set("Architecture", &*options.architecture);
+        if let Some(multi_arch) = options.multi_arch {
+            control.set("Multi-Arch", multi_arch.to_string());
+            for path in options.multi_arch_conflicts() {
+                self.listener.warning(format!("/{} is not in an architecture-specific directory, and will conflict with the {} package of other architectures (Multi-Arch: same)", path.display(), options.deb_name));
+            }
+        }
+        if let Some(ref package_type) = options.package_type {
+            control.set("Package-Type", &**package_type);
+            if package_type == "ddeb" {
+                control.set("Auto-Built-Package", "debug-symbols");
+                if !options.build_ids.is_empty() {
+                    control.set("Build-Ids", options.build_ids.join(" "));
+                }
+            }
+        }
         if let Some(ref repo) = options.repository {
             if repo.starts_with("http") {
-                writeln!(&mut control, "Vcs-Browser: {repo}")?;
+                control.set("Vcs-Browser", &**repo);
             }
             if let Some(kind) = options.repository_type() {
-                writeln!(&mut control, "Vcs-{kind}: {repo}")?;
+                control.set(&format!("Vcs-{kind}"), &**repo);
             }
         }
         if let Some(homepage) = options.homepage.as_ref().or(options.documentation.as_ref()) {
-            writeln!(&mut control, "Homepage: {homepage}")?;
+            control.set("Homepage", &**homepage);
         }
         if let Some(ref section) = options.section {
-            writeln!(&mut control, "Section: {section}")?;
+            control.set("Section", &**section);
         }
-        writeln!(&mut control, "Priority: {}", options.priority)?;
-        writeln!(&mut control, "Maintainer: {}", options.maintainer)?;
+        control.set("Priority", &*options.priority);
+        control.set("Maintainer", &*options.maintainer);
 
         let installed_size = options.assets.resolved
             .iter()
             .map(|m| (m.source.file_size().unwrap_or(0)+2047)/1024) // assume 1KB of fs overhead per file
             .sum::<u64>();
 
-        writeln!(&mut control, "Installed-Size: {installed_size}")?;
+        control.set("Installed-Size", installed_size.to_string());
 
         let deps = options.get_dependencies(self.listener)?;
         if !deps.is_empty() {
-            writeln!(&mut control, "Depends: {deps}")?;
-        }
-
-        if let Some(ref pre_depends) = options.pre_depends {
-            let pre_depends_normalized = pre_depends.trim();
-
-            if !pre_depends_normalized.is_empty() {
-                writeln!(&mut control, "Pre-Depends: {pre_depends_normalized}")?;
-            }
+            control.set("Depends", deps);
         }
 
-        if let Some(ref recommends) = options.recommends {
-            let recommends_normalized = recommends.trim();
-
-            if !recommends_normalized.is_empty() {
-                writeln!(&mut control, "Recommends: {recommends_normalized}")?;
+        for (field, value) in options.relation_fields() {
+            let relations = options.get_relations(field, value)?;
+            if !relations.is_empty() {
+                control.set(field.name(), relations);
             }
         }
 
-        if let Some(ref suggests) = options.suggests {
-            let suggests_normalized = suggests.trim();
-
-            if !suggests_normalized.is_empty() {
-                writeln!(&mut control, "Suggests: {suggests_normalized}")?;
-            }
-        }
-
-        if let Some(ref enhances) = options.enhances {
-            let enhances_normalized = enhances.trim();
-
-            if !enhances_normalized.is_empty() {
-                writeln!(&mut control, "Enhances: {enhances_normalized}")?;
-            }
-        }
-
-        if let Some(ref conflicts) = options.conflicts {
-            writeln!(&mut control, "Conflicts: {conflicts}")?;
-        }
-        if let Some(ref breaks) = options.breaks {
-            writeln!(&mut control, "Breaks: {breaks}")?;
-        }
-        if let Some(ref replaces) = options.replaces {
-            writeln!(&mut control, "Replaces: {replaces}")?;
-        }
-        if let Some(ref provides) = options.provides {
-            writeln!(&mut control, "Provides: {provides}")?;
+        if let Some(crates) = options.static_built_using() {
+            control.set("Static-Built-Using", crates);
         }
 
-        write!(&mut control, "Description:")?;
-        for line in options.description.split_by_chars(79) {
-            writeln!(&mut control, " {line}")?;
+        for (name, value) in &options.extra_fields {
+            control.set(name, &**value);
         }
 
+        let mut description = options.description.split_by_chars(79);
         if let Some(ref desc) = options.extended_description {
-            for line in desc.split_by_chars(79) {
-                writeln!(&mut control, " {line}")?;
-            }
+            description.extend(desc.split_by_chars(79));
         }
+        control.set("Description", description.join("\n"));
+
+        let mut control = control.to_string().into_bytes();
         control.push(10);
 
         // Add the control file to the tar archive.
@@ -255,6 +289,10 @@ impl<'l, W: Write> ControlArchiveBuilder<'l, W> {
     }
 }
 
+fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
 #[cfg(test)]
 mod tests {
     // The following test suite verifies that `fn generate_scripts()` correctly
@@ -317,6 +355,8 @@ mod tests {
 
     fn prepare<'l, W: Write>(dest: W, package_name: Option<&str>, mock_listener: &'l mut MockListener) -> (Config, ControlArchiveBuilder<'l, W>) {
         mock_listener.expect_info().return_const(());
+        // the test packages have a placeholder instead of an SPDX license
+        mock_listener.expect_warning().withf(|w| w.contains("'Some license' is not a valid SPDX expression")).return_const(());
 
         let mut config = Config::from_manifest(
             Path::new("test-resources/testroot/Cargo.toml"),
@@ -365,6 +405,31 @@ mod tests {
         assert!(archived_file_names.is_empty());
     }
 
+    #[test]
+    fn generate_scripts_chowns_assets_owned_by_named_accounts() {
+        let mut listener = MockListener::new();
+        let (mut config, mut in_ar) = prepare(vec![], None, &mut listener);
+
+        let asset = |path: &str, chmod, owner: &str| Asset::new(AssetSource::Data(vec![]), PathBuf::from(path), chmod, IsBuilt::No)
+            .with_owner(Some(owner.parse().unwrap()));
+        config.assets.resolved.push(asset("var/lib/app/state", 0o644, "app:app"));
+        config.assets.resolved.push(asset("usr/bin/helper", 0o4755, "app:app"));
+        config.assets.resolved.push(asset("etc/app's.conf", 0o640, "root:adm"));
+        config.assets.resolved.push(asset("usr/bin/app", 0o755, "0:0"));
+
+        in_ar.generate_scripts(&config).unwrap();
+        let archive_bytes = in_ar.finish().unwrap();
+        let archived_content = extract_contents(&mut tar::Archive::new(&archive_bytes[..]));
+
+        assert_eq!(1, archived_content.len());
+        let postinst = &archived_content["postinst"];
+        assert!(postinst.starts_with("#!/bin/sh\nset -e\n"));
+        assert!(postinst.contains("chown app:app '/var/lib/app/state' '/usr/bin/helper'\n"));
+        assert!(postinst.contains("chmod 4755 '/usr/bin/helper'\n"));
+        assert!(postinst.contains("chown root:adm '/etc/app'\\''s.conf'\n"));
+        assert!(!postinst.contains("/usr/bin/app"));
+    }
+
     #[test]
     fn generate_scripts_archives_user_supplied_maintainer_scripts_in_root_package() {
         let maintainer_script_paths = vec![
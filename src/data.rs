@@ -3,18 +3,66 @@ use crate::error::*;
 use crate::listener::Listener;
 use crate::manifest::{Asset, Config, IsBuilt, AssetSource};
 use crate::tararchive::Archive;
-use flate2::Compression;
+use flate2::{Compression, GzBuilder};
 use md5::Digest;
+use sha2::Digest as _;
 use std::collections::HashMap;
 use std::fs;
 use std::io::Write;
 use std::path::{Path, PathBuf};
-use flate2::bufread::GzEncoder;
+
+/// Hashes of a file in the data archive
+#[derive(Debug, Clone)]
+pub struct FileHashes {
+    /// For `md5sums`
+    pub md5: Digest,
+    /// Hex-encoded SHA-1 and SHA-256, only computed for SBOMs
+    pub sha: Option<(String, String)>,
+}
+
+/// Computes hashes of the data written to it
+struct Hasher {
+    md5: md5::Context,
+    sha: Option<(sha1::Sha1, sha2::Sha256)>,
+}
+
+impl Hasher {
+    fn new(with_sha: bool) -> Self {
+        Self { md5: md5::Context::new(), sha: if with_sha { Some(Default::default()) } else { None } }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.md5.consume(data);
+        if let Some((sha1, sha256)) = &mut self.sha {
+            sha1.update(data);
+            sha256.update(data);
+        }
+    }
+
+    fn finish(self) -> FileHashes {
+        FileHashes {
+            md5: self.md5.compute(),
+            sha: self.sha.map(|(sha1, sha256)| (format!("{:x}", sha1.finalize()), format!("{:x}", sha256.finalize()))),
+        }
+    }
+}
 
 /// Generates an uncompressed tar archive and hashes of its files
-pub fn generate_archive<W: Write>(dest: W, options: &Config, time: u64, listener: &dyn Listener) -> CDResult<(W, HashMap<PathBuf, Digest>)> {
+pub fn generate_archive<W: Write>(dest: W, options: &Config, time: u64, listener: &dyn Listener) -> CDResult<(W, HashMap<PathBuf, FileHashes>)> {
     let mut archive = Archive::new(dest, time);
-    let copy_hashes = archive_files(&mut archive, options, listener)?;
+    let mut copy_hashes = archive_files(&mut archive, options, listener)?;
+    if options.install_sbom {
+        // Added last, since they list hashes of all the other files
+        for &format in &options.sbom {
+            let sbom = crate::sbom::generate(format, options, &copy_hashes, time)?;
+            let path = crate::sbom::installed_path(options, format);
+            listener.info(format!("SBOM -> {}", path.display()));
+            archive.file_from_reader(&path, sbom.len() as u64, &mut &sbom[..], 0o644, None)?;
+            let mut hasher = Hasher::new(true);
+            hasher.update(&sbom);
+            copy_hashes.insert(path, hasher.finish());
+        }
+    }
     Ok((archive.into_inner()?, copy_hashes))
 }
 
@@ -24,7 +72,7 @@ pub(crate) fn generate_changelog_asset(options: &Config) -> CDResult<Option<Vec<
         let changelog = fs::read(options.path_in_package(path))
             .and_then(|content| {
                 // The input is plaintext, but the debian package should contain gzipped one.
-                gzipped(&content)
+                gzipped(&content, options.source_date_epoch.unwrap_or(0))
             })
             .map_err(move |e| CargoDebError::IoFile("unable to read changelog file", e, path.into()))?;
         Ok(Some(changelog))
@@ -41,7 +89,7 @@ fn append_copyright_metadata(copyright: &mut Vec<u8>, options: &Config) -> Resul
     }
     writeln!(copyright, "Copyright: {}", options.copyright)?;
     if let Some(ref license) = options.license {
-        writeln!(copyright, "License: {license}")?;
+        writeln!(copyright, "License: {}", crate::spdx::debian_license(license))?;
     }
     Ok(())
 }
@@ -49,10 +97,13 @@ fn append_copyright_metadata(copyright: &mut Vec<u8>, options: &Config) -> Resul
 /// Generates the copyright file from the license file and adds that to the tar archive.
 pub(crate) fn generate_copyright_asset(options: &Config) -> CDResult<Vec<u8>> {
     let mut copyright: Vec<u8> = Vec::new();
+    // A license file in the DEP-5 format already has paragraphs for the package's own files
+    let mut license_file_has_metadata = false;
     if let Some(ref path) = options.license_file {
         let license_string = fs::read_to_string(options.path_in_package(path))
             .map_err(|e| CargoDebError::IoFile("unable to read license file", e, path.to_owned()))?;
-        if !has_copyright_metadata(&license_string) {
+        license_file_has_metadata = has_copyright_metadata(&license_string);
+        if !license_file_has_metadata {
             append_copyright_metadata(&mut copyright, options)?;
         }
 
@@ -70,6 +121,16 @@ pub(crate) fn generate_copyright_asset(options: &Config) -> CDResult<Vec<u8>> {
         append_copyright_metadata(&mut copyright, options)?;
     }
 
+    let mut paragraphs = String::new();
+    if options.copyright_dependencies && !options.linked_crates.is_empty() {
+        let license = if license_file_has_metadata { None } else { options.license.as_deref() };
+        crate::dep5::append_linked_crates(&mut paragraphs, &options.copyright, license, &options.linked_crates);
+    } else if let (None, Some(license)) = (&options.license_file, &options.license) {
+        // Without a license file, the texts can still be found on every Debian system
+        crate::dep5::append_common_licenses(&mut paragraphs, license);
+    }
+    copyright.extend_from_slice(paragraphs.as_bytes());
+
     // Write a copy to the disk for the sake of obtaining a md5sum for the control archive.
     Ok(copyright)
 }
@@ -99,10 +160,10 @@ pub fn compress_assets(options: &mut Config, listener: &dyn Listener) -> CDResul
 
     for (idx, asset) in options.assets.resolved.iter().enumerate() {
         let target_path_str = asset.c.target_path.to_string_lossy();
-        if needs_compression(&target_path_str) {
+        if !asset.source.is_dir() && needs_compression(&target_path_str) {
             listener.info(format!("Compressing '{}'", asset.source.path().unwrap_or_else(|| Path::new("-")).display()));
             new_assets.push(Asset::new(
-                crate::manifest::AssetSource::Data(gzipped(&asset.source.data()?)?),
+                crate::manifest::AssetSource::Data(gzipped(&asset.source.data()?, options.source_date_epoch.unwrap_or(0))?),
                 format!("{target_path_str}.gz").into(),
                 asset.c.chmod,
                 IsBuilt::No,
@@ -117,50 +178,90 @@ pub fn compress_assets(options: &mut Config, listener: &dyn Listener) -> CDResul
     }
 
     options.assets.resolved.append(&mut new_assets);
+    options.sort_assets_by_type();
 
     Ok(())
 }
 
 /// Copies all the files to be packaged into the tar archive.
 /// Returns MD5 hashes of files copied
-fn archive_files<W: Write>(archive: &mut Archive<W>, options: &Config, listener: &dyn Listener) -> CDResult<HashMap<PathBuf, Digest>> {
-    let (send, recv) = crossbeam_channel::bounded(2);
-    std::thread::scope(move |s| {
-        let num_items = options.assets.resolved.len();
-        let hash_thread = s.spawn(move || {
-            let mut hashes = HashMap::with_capacity(num_items);
-            hashes.extend(recv.into_iter().map(|(path, data)| {
-                (path, md5::compute(&data))
-            }));
-            hashes
-        });
-        for asset in &options.assets.resolved {
-            let mut log_line = format!("{} -> {}",
-                asset.source.path().unwrap_or_else(|| Path::new("-")).display(),
-                asset.c.target_path.display()
-            );
-            if let Some(len) = asset.source.file_size() {
-                let (size, unit) = human_size(len);
-                use std::fmt::Write;
-                let _ = write!(&mut log_line, " ({size}{unit})");
-            }
-            listener.info(log_line);
+///
+/// Files on disk are streamed into the archive and hashed on the way,
+/// so memory use doesn't depend on the size of the assets.
+fn archive_files<W: Write>(archive: &mut Archive<W>, options: &Config, listener: &dyn Listener) -> CDResult<HashMap<PathBuf, FileHashes>> {
+    let mut hashes = HashMap::with_capacity(options.assets.resolved.len());
+    let with_sha = !options.sbom.is_empty();
+    // Explicit directories go first, so that they're not created implicitly with the default mode
+    // when adding their files. Parents sort before their subdirectories.
+    let mut dirs: Vec<_> = options.assets.resolved.iter().filter(|a| a.source.is_dir()).collect();
+    dirs.sort_by(|a, b| a.c.target_path.cmp(&b.c.target_path));
+    let files = options.assets.resolved.iter().filter(|a| !a.source.is_dir());
+    for asset in dirs.into_iter().chain(files) {
+        let mut log_line = format!("{} -> {}",
+            asset.source.path().unwrap_or_else(|| Path::new("-")).display(),
+            asset.c.target_path.display()
+        );
+        if let Some(len) = asset.source.file_size() {
+            let (size, unit) = human_size(len);
+            use std::fmt::Write;
+            let _ = write!(&mut log_line, " ({size}{unit})");
+        }
+        listener.info(log_line);
 
-            match &asset.source {
-                AssetSource::Symlink(source_path) => {
-                    let link_name = fs::read_link(source_path)?;
-                    archive.symlink(&asset.c.target_path, &link_name)?;
-                }
-                _ => {
-                    let out_data = asset.source.data()?;
-                    archive.file(&asset.c.target_path, &out_data, asset.c.chmod)?;
-                    send.send((asset.c.target_path.clone(), out_data)).unwrap();
-                },
+        match &asset.source {
+            AssetSource::Symlink(source_path) => {
+                let link_name = fs::read_link(source_path)?;
+                archive.symlink(&asset.c.target_path, &link_name, asset.c.owner.as_ref())?;
+            }
+            AssetSource::SymlinkTo(link_name) => {
+                archive.symlink(&asset.c.target_path, link_name, asset.c.owner.as_ref())?;
             }
+            AssetSource::Path(source_path) => {
+                let file = fs::File::open(source_path)
+                    .map_err(|e| CargoDebError::IoFile("unable to read asset to add to archive", e, source_path.clone()))?;
+                let size = file.metadata()?.len();
+                let mut reader = HashingReader::new(io::BufReader::with_capacity(1<<16, file).take(size), with_sha);
+                archive.file_from_reader(&asset.c.target_path, size, &mut reader, asset.c.chmod, asset.c.owner.as_ref())?;
+                if reader.bytes_read != size {
+                    let err = io::Error::new(io::ErrorKind::UnexpectedEof, "file has been truncated while it was being archived");
+                    return Err(CargoDebError::IoFile("unable to read asset to add to archive", err, source_path.clone()));
+                }
+                hashes.insert(asset.c.target_path.clone(), reader.hasher.finish());
+            },
+            AssetSource::Data(data) => {
+                archive.file_from_reader(&asset.c.target_path, data.len() as u64, &mut &data[..], asset.c.chmod, asset.c.owner.as_ref())?;
+                let mut hasher = Hasher::new(with_sha);
+                hasher.update(data);
+                hashes.insert(asset.c.target_path.clone(), hasher.finish());
+            },
+            AssetSource::Dir => {
+                archive.explicit_directory(&asset.c.target_path, asset.c.chmod, asset.c.owner.as_ref())?;
+            },
         }
-        drop(send);
-        Ok(hash_thread.join().unwrap())
-    })
+    }
+    Ok(hashes)
+}
+
+/// Computes hashes of everything read through it
+struct HashingReader<R> {
+    inner: R,
+    hasher: Hasher,
+    bytes_read: u64,
+}
+
+impl<R: Read> HashingReader<R> {
+    fn new(inner: R, with_sha: bool) -> Self {
+        Self { inner, hasher: Hasher::new(with_sha), bytes_read: 0 }
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.hasher.update(&buf[..len]);
+        self.bytes_read += len as u64;
+        Ok(len)
+    }
 }
 
 fn human_size(len: u64) -> (u64, &'static str) {
@@ -173,8 +274,12 @@ fn human_size(len: u64) -> (u64, &'static str) {
     ((len + 999_999) / 1_000_000, "MB")
 }
 
-fn gzipped(content: &[u8]) -> io::Result<Vec<u8>> {
+/// `mtime` is stored in the gzip header. 0 means no timestamp, like `gzip -n`.
+fn gzipped(content: &[u8], mtime: u64) -> io::Result<Vec<u8>> {
     let mut compressed = Vec::with_capacity(content.len() * 2 / 3);
-    GzEncoder::new(content, Compression::default()).read_to_end(&mut compressed)?;
+    GzBuilder::new()
+        .mtime(mtime as u32)
+        .buf_read(content, Compression::default())
+        .read_to_end(&mut compressed)?;
     Ok(compressed)
 }
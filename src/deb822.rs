@@ -0,0 +1,164 @@
+//! Paragraphs of `control` files in the deb822 format
+//! <https://manpages.debian.org/deb822>
+
+use crate::error::*;
+use std::fmt;
+
+/// Order of fields in binary package control files, similar to what `dpkg-gencontrol` writes.
+/// Fields not listed here are written before `Description`.
+const FIELD_ORDER: &[&str] = &[
+    "Package",
+    "Source",
+    "Version",
+    "Architecture",
+    "Multi-Arch",
+    "Essential",
+    "Protected",
+    "Important",
+    "Build-Essential",
+    "Package-Type",
+    "Auto-Built-Package",
+    "Build-Ids",
+    "Vcs-Browser",
+    "Vcs-Arch",
+    "Vcs-Bzr",
+    "Vcs-Cvs",
+    "Vcs-Darcs",
+    "Vcs-Git",
+    "Vcs-Hg",
+    "Vcs-Mtn",
+    "Vcs-Svn",
+    "Homepage",
+    "Section",
+    "Priority",
+    "Origin",
+    "Bugs",
+    "Maintainer",
+    "Installed-Size",
+    "Depends",
+    "Pre-Depends",
+    "Recommends",
+    "Suggests",
+    "Enhances",
+    "Conflicts",
+    "Breaks",
+    "Replaces",
+    "Provides",
+    "Built-Using",
+    "Static-Built-Using",
+    "Rules-Requires-Root",
+    "Tag",
+    "Task",
+    "Description",
+];
+
+/// Fields that can be set in `extra-fields`. Others are generated from their own settings.
+const EXTRA_FIELDS: &[&str] = &[
+    "Source",
+    "Essential",
+    "Protected",
+    "Important",
+    "Build-Essential",
+    "Origin",
+    "Bugs",
+    "Built-Using",
+    "Rules-Requires-Root",
+    "Tag",
+    "Task",
+];
+
+/// Fields of one package, e.g. the `control` file of a `.deb`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct ControlParagraph {
+    fields: Vec<(String, String)>,
+}
+
+impl ControlParagraph {
+    /// Adds or replaces a field. Values can have multiple lines, which are written as continuation lines.
+    pub fn set(&mut self, name: &str, value: impl Into<String>) {
+        let value = value.into();
+        if let Some(existing) = self.fields.iter_mut().find(|(n, _)| n.eq_ignore_ascii_case(name)) {
+            existing.1 = value;
+            return;
+        }
+        let rank = field_rank(name);
+        let pos = self.fields.iter().position(|(n, _)| field_rank(n) > rank).unwrap_or(self.fields.len());
+        self.fields.insert(pos, (name.to_owned(), value));
+    }
+}
+
+fn field_rank(name: &str) -> usize {
+    match FIELD_ORDER.iter().position(|f| f.eq_ignore_ascii_case(name)) {
+        Some(pos) => pos * 2,
+        // between the last field and `Description`
+        None => (FIELD_ORDER.len() - 1) * 2 - 1,
+    }
+}
+
+impl fmt::Display for ControlParagraph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, value) in &self.fields {
+            let mut lines = value.lines();
+            write!(f, "{name}:")?;
+            match lines.next() {
+                Some(first) if !first.trim().is_empty() => writeln!(f, " {}", first.trim_end())?,
+                _ => writeln!(f)?,
+            }
+            for line in lines {
+                let line = line.trim_end();
+                // empty lines would end the paragraph
+                if line.trim_start().is_empty() {
+                    writeln!(f, " .")?;
+                } else {
+                    writeln!(f, " {line}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Checks a field name from `extra-fields`, and returns it in its canonical capitalization.
+/// Apart from the known fields, anything starting with `X-` is allowed.
+pub(crate) fn check_extra_field(name: &str, value: &str) -> CDResult<String> {
+    let invalid = |reason: String| CargoDebError::InvalidControlField(name.to_owned(), reason);
+    if name.is_empty() || name.starts_with(['#', '-']) || !name.chars().all(|c| c.is_ascii_graphic() && c != ':') {
+        return Err(invalid("field names must be printable ASCII without spaces or ':'".into()));
+    }
+    if value.trim().is_empty() {
+        return Err(invalid("the value is empty".into()));
+    }
+    if let Some(known) = EXTRA_FIELDS.iter().find(|f| f.eq_ignore_ascii_case(name)) {
+        return Ok((*known).to_owned());
+    }
+    if name.len() > 2 && name[..2].eq_ignore_ascii_case("x-") {
+        return Ok(name.to_owned());
+    }
+    if FIELD_ORDER.iter().any(|f| f.eq_ignore_ascii_case(name)) {
+        return Err(invalid("this field is generated by cargo-deb; use its own setting instead".into()));
+    }
+    Err(invalid(format!("unknown field; expected one of {}, or a custom field starting with X-", EXTRA_FIELDS.join(", "))))
+}
+
+#[test]
+fn paragraph_format() {
+    let mut p = ControlParagraph::default();
+    p.set("Package", "foo");
+    p.set("Description", "synopsis\nfirst line\n\n  indented");
+    p.set("X-Custom", "1");
+    p.set("Version", "1.0");
+    p.set("Multi-Arch", "same");
+    p.set("package", "bar");
+    assert_eq!("Package: bar\nVersion: 1.0\nMulti-Arch: same\nX-Custom: 1\nDescription: synopsis\n first line\n .\n   indented\n", p.to_string());
+}
+
+#[test]
+fn extra_field_names() {
+    assert_eq!("Essential", check_extra_field("essential", "no").unwrap());
+    assert!(check_extra_field("Multi-Arch", "same").is_err());
+    assert_eq!("X-Foo-Bar", check_extra_field("X-Foo-Bar", "baz").unwrap());
+    assert!(check_extra_field("Depends", "foo").is_err());
+    assert!(check_extra_field("Frobnicate", "yes").is_err());
+    assert!(check_extra_field("X-Bad Name", "yes").is_err());
+    assert!(check_extra_field("Essential", " ").is_err());
+}
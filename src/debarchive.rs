@@ -3,6 +3,7 @@ use crate::error::CDResult;
 use crate::manifest::Config;
 use std::fs::File;
 use std::fs;
+use std::io::Read;
 use std::path::PathBuf;
 
 pub struct DebArchive {
@@ -12,7 +13,7 @@ pub struct DebArchive {
 
 impl DebArchive {
     pub fn new(config: &Config) -> CDResult<Self> {
-        let out_filename = format!("{}_{}_{}.deb", config.deb_name, config.deb_version, config.architecture);
+        let out_filename = format!("{}_{}_{}.{}", config.deb_name, config.deb_version, config.architecture, Self::extension(config));
         let out_abspath = config.deb_output_path(&out_filename);
         {
             let deb_dir = out_abspath.parent().ok_or("invalid dir")?;
@@ -27,11 +28,25 @@ impl DebArchive {
     }
 
     pub(crate) fn filename_glob(config: &Config) -> String {
-        format!("{}_*_{}.deb", config.deb_name, config.architecture)
+        format!("{}_*_{}.{}", config.deb_name, config.architecture, Self::extension(config))
+    }
+
+    pub(crate) fn dbgsym_filename_glob(config: &Config) -> String {
+        format!("{}-dbgsym_*_{}.ddeb", config.deb_name, config.architecture)
+    }
+
+    /// Debug symbol packages are `.ddeb`, so that they can be kept apart from regular packages
+    fn extension(config: &Config) -> &'static str {
+        if config.package_type.as_deref() == Some("ddeb") { "ddeb" } else { "deb" }
     }
 
     pub fn add_data(&mut self, dest_path: String, mtime_timestamp: u64, data: &[u8]) -> CDResult<()> {
-        let mut header = Header::new(dest_path.into(), data.len() as u64);
+        self.add_file(dest_path, mtime_timestamp, data.len() as u64, data)
+    }
+
+    /// Appends `size` bytes read from `data`, e.g. a compressed archive stored in a temporary file
+    pub fn add_file(&mut self, dest_path: String, mtime_timestamp: u64, size: u64, data: impl Read) -> CDResult<()> {
+        let mut header = Header::new(dest_path.into(), size);
         header.set_mode(0o644);
         header.set_mtime(mtime_timestamp);
         header.set_uid(0);
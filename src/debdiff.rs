@@ -0,0 +1,311 @@
+use crate::debreader::{mode_string, ControlFile, DataFile, DebPackage};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// Differences between two versions of a package, from `diff()`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PackageDiff {
+    /// Fields of the `control` file that have been added, removed or changed
+    pub control: Vec<FieldChange>,
+    /// Maintainer scripts and other control files, except `control` and `md5sums`
+    pub control_files: Vec<ControlFileChange>,
+    pub added_files: Vec<DataFile>,
+    pub removed_files: Vec<DataFile>,
+    pub changed_files: Vec<FileChange>,
+    /// Total size of regular files
+    pub old_size: u64,
+    pub new_size: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldChange {
+    pub name: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ControlFileChange {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old: Option<ControlFile>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new: Option<ControlFile>,
+    /// Line-by-line diff of the content, prefixed with `+`, `-` or ` `
+    pub diff: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileChange {
+    pub path: String,
+    /// Which properties of the file are different
+    pub changes: Vec<FileAttribute>,
+    pub old: DataFile,
+    pub new: DataFile,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FileAttribute {
+    Kind,
+    Mode,
+    Owner,
+    Size,
+    Content,
+    LinkTarget,
+}
+
+impl PackageDiff {
+    /// No differences in control fields, scripts and files
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.control.is_empty() && self.control_files.is_empty() &&
+        self.added_files.is_empty() && self.removed_files.is_empty() && self.changed_files.is_empty()
+    }
+
+    #[must_use]
+    pub fn size_delta(&self) -> i64 {
+        self.new_size as i64 - self.old_size as i64
+    }
+}
+
+/// Compares two packages, e.g. before and after an upgrade of dependencies.
+/// Files are matched by path, and control fields by name.
+#[must_use]
+pub fn diff(old: &DebPackage, new: &DebPackage) -> PackageDiff {
+    let old_by_path = files_by_path(&old.files);
+    let new_by_path = files_by_path(&new.files);
+    PackageDiff {
+        control: diff_control_fields(old, new),
+        control_files: diff_control_files(&old.control_files, &new.control_files),
+        added_files: new.files.iter().filter(|f| !old_by_path.contains_key(f.path.as_str())).cloned().collect(),
+        removed_files: old.files.iter().filter(|f| !new_by_path.contains_key(f.path.as_str())).cloned().collect(),
+        changed_files: diff_files(&old_by_path, &new.files),
+        old_size: old.data_size(),
+        new_size: new.data_size(),
+    }
+}
+
+fn diff_control_fields(old: &DebPackage, new: &DebPackage) -> Vec<FieldChange> {
+    // old order first, then fields only in the new package
+    let mut names: Vec<&str> = old.control.0.iter().map(|(n, _)| n.as_str()).collect();
+    for (name, _) in &new.control.0 {
+        if !names.iter().any(|n| n.eq_ignore_ascii_case(name)) {
+            names.push(name);
+        }
+    }
+    names.into_iter().filter_map(|name| {
+        let old_value = old.field(name);
+        let new_value = new.field(name);
+        if old_value == new_value {
+            return None;
+        }
+        Some(FieldChange {
+            name: name.to_owned(),
+            old: old_value.map(From::from),
+            new: new_value.map(From::from),
+        })
+    }).collect()
+}
+
+fn diff_control_files(old: &[ControlFile], new: &[ControlFile]) -> Vec<ControlFileChange> {
+    // control is compared field by field, and md5sums duplicates the list of files
+    let relevant = |f: &&ControlFile| f.name != "control" && f.name != "md5sums";
+    let mut by_name: BTreeMap<&str, (Option<&ControlFile>, Option<&ControlFile>)> = BTreeMap::new();
+    for f in old.iter().filter(relevant) {
+        by_name.entry(&f.name).or_default().0 = Some(f);
+    }
+    for f in new.iter().filter(relevant) {
+        by_name.entry(&f.name).or_default().1 = Some(f);
+    }
+    by_name.into_iter().filter(|(_, (o, n))| o != n).map(|(name, (o, n))| {
+        ControlFileChange {
+            name: name.to_owned(),
+            diff: diff_lines(o.map_or("", |f| &f.content), n.map_or("", |f| &f.content)),
+            old: o.cloned(),
+            new: n.cloned(),
+        }
+    }).collect()
+}
+
+fn files_by_path(files: &[DataFile]) -> HashMap<&str, &DataFile> {
+    files.iter().map(|f| (f.path.as_str(), f)).collect()
+}
+
+fn diff_files(old_by_path: &HashMap<&str, &DataFile>, new: &[DataFile]) -> Vec<FileChange> {
+    new.iter().filter_map(|n| {
+        let o = *old_by_path.get(n.path.as_str())?;
+        let mut changes = Vec::new();
+        if o.kind != n.kind { changes.push(FileAttribute::Kind); }
+        if o.mode != n.mode { changes.push(FileAttribute::Mode); }
+        if o.owner != n.owner { changes.push(FileAttribute::Owner); }
+        if o.size != n.size { changes.push(FileAttribute::Size); }
+        if o.md5 != n.md5 { changes.push(FileAttribute::Content); }
+        if o.link_target != n.link_target { changes.push(FileAttribute::LinkTarget); }
+        if changes.is_empty() {
+            return None;
+        }
+        Some(FileChange {
+            path: n.path.clone(),
+            changes,
+            old: o.clone(),
+            new: n.clone(),
+        })
+    }).collect()
+}
+
+/// Minimal line diff based on the longest common subsequence. Maintainer scripts are short.
+fn diff_lines(old: &str, new: &str) -> Vec<String> {
+    let old: Vec<_> = old.lines().collect();
+    let new: Vec<_> = new.lines().collect();
+    let mut lcs = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+    let mut out = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            out.push(format!(" {}", old[i]));
+            i += 1; j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            out.push(format!("-{}", old[i]));
+            i += 1;
+        } else {
+            out.push(format!("+{}", new[j]));
+            j += 1;
+        }
+    }
+    out
+}
+
+impl fmt::Display for PackageDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No differences");
+        }
+        if !self.control.is_empty() {
+            writeln!(f, "Control fields:")?;
+            for field in &self.control {
+                match (&field.old, &field.new) {
+                    (Some(old), Some(new)) if !old.contains('\n') && !new.contains('\n') => {
+                        writeln!(f, "  ~ {}: {old} -> {new}", field.name)?;
+                    },
+                    (Some(old), Some(new)) => {
+                        writeln!(f, "  ~ {}:", field.name)?;
+                        for line in diff_lines(old, new).iter().filter(|l| !l.starts_with(' ')) {
+                            writeln!(f, "    {line}")?;
+                        }
+                    },
+                    (None, Some(new)) => writeln!(f, "  + {}: {new}", field.name)?,
+                    (Some(old), None) => writeln!(f, "  - {}: {old}", field.name)?,
+                    (None, None) => {},
+                }
+            }
+        }
+        if !self.control_files.is_empty() {
+            writeln!(f, "Control files:")?;
+            for file in &self.control_files {
+                match (&file.old, &file.new) {
+                    (None, Some(_)) => writeln!(f, "  + {}", file.name)?,
+                    (Some(_), None) => writeln!(f, "  - {}", file.name)?,
+                    (Some(old), Some(new)) if old.mode != new.mode => {
+                        writeln!(f, "  ~ {} (mode {:04o} -> {:04o})", file.name, old.mode, new.mode)?;
+                    },
+                    _ => writeln!(f, "  ~ {}", file.name)?,
+                }
+                for line in &file.diff {
+                    writeln!(f, "    {line}")?;
+                }
+            }
+        }
+        if !self.added_files.is_empty() || !self.removed_files.is_empty() || !self.changed_files.is_empty() {
+            writeln!(f, "Files:")?;
+            for file in &self.added_files {
+                writeln!(f, "  + {} /{} ({} bytes)", mode_string(file.kind, file.mode), file.path, file.size)?;
+            }
+            for file in &self.removed_files {
+                writeln!(f, "  - {} /{} ({} bytes)", mode_string(file.kind, file.mode), file.path, file.size)?;
+            }
+            for file in &self.changed_files {
+                let (o, n) = (&file.old, &file.new);
+                let mut details: Vec<_> = file.changes.iter().filter_map(|c| Some(match c {
+                    FileAttribute::Kind | FileAttribute::Mode => format!("{} -> {}", mode_string(o.kind, o.mode), mode_string(n.kind, n.mode)),
+                    FileAttribute::Owner => format!("owner {} -> {}", o.owner, n.owner),
+                    FileAttribute::Size => format!("size {} -> {} ({:+})", o.size, n.size, n.size as i64 - o.size as i64),
+                    FileAttribute::Content if file.changes.contains(&FileAttribute::Size) => return None,
+                    FileAttribute::Content => "content".into(),
+                    FileAttribute::LinkTarget => format!("-> {} (was {})",
+                        n.link_target.as_deref().unwrap_or("-"), o.link_target.as_deref().unwrap_or("-")),
+                })).collect();
+                // kind and mode are shown together
+                details.dedup();
+                writeln!(f, "  ~ /{}: {}", file.path, details.join(", "))?;
+            }
+        }
+        writeln!(f, "Size: {} -> {} bytes ({:+})", self.old_size, self.new_size, self.size_delta())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::debreader::tests::test_deb;
+    use crate::debreader::FileKind;
+
+    #[test]
+    fn diff_packages() {
+        let old = test_deb("Package: test\nVersion: 1.0\nDepends: libc6\n", &[("postinst", "#!/bin/sh\nset -e\necho old\n")], &[
+            ("usr/bin/app", b"hello\n", 0o755),
+            ("usr/share/doc/test/README", b"readme", 0o644),
+        ]);
+        let new = test_deb("Package: test\nVersion: 1.1\nRecommends: foo\n", &[("postinst", "#!/bin/sh\nset -e\necho new\n"), ("prerm", "#!/bin/sh\n")], &[
+            ("usr/bin/app", b"hello world\n", 0o700),
+            ("usr/share/doc/test/README", b"readme", 0o644),
+            ("usr/share/doc/test/NEWS", b"news", 0o644),
+        ]);
+        let old = DebPackage::read(&old[..]).unwrap();
+        let new = DebPackage::read(&new[..]).unwrap();
+        let d = diff(&old, &new);
+        assert!(!d.is_empty());
+
+        assert_eq!(vec![
+            FieldChange { name: "Version".into(), old: Some("1.0".into()), new: Some("1.1".into()) },
+            FieldChange { name: "Depends".into(), old: Some("libc6".into()), new: None },
+            FieldChange { name: "Recommends".into(), old: None, new: Some("foo".into()) },
+        ], d.control);
+
+        assert_eq!(2, d.control_files.len());
+        assert_eq!("postinst", d.control_files[0].name);
+        assert_eq!(vec![" #!/bin/sh", " set -e", "-echo old", "+echo new"], d.control_files[0].diff);
+        assert_eq!("prerm", d.control_files[1].name);
+        assert!(d.control_files[1].old.is_none());
+
+        assert_eq!(1, d.added_files.len());
+        assert_eq!("usr/share/doc/test/NEWS", d.added_files[0].path);
+        assert!(d.removed_files.is_empty());
+        assert_eq!(1, d.changed_files.len());
+        assert_eq!(vec![FileAttribute::Mode, FileAttribute::Size, FileAttribute::Content], d.changed_files[0].changes);
+        assert_eq!(FileKind::File, d.changed_files[0].new.kind);
+        assert_eq!(10, d.size_delta());
+
+        let text = d.to_string();
+        assert!(text.contains("  ~ Version: 1.0 -> 1.1\n"), "{text}");
+        assert!(text.contains("  ~ /usr/bin/app: -rwxr-xr-x -> -rwx------, size 6 -> 12 (+6)\n"), "{text}");
+        assert!(text.contains("    +echo new\n"), "{text}");
+
+        let json = serde_json::to_value(&d).unwrap();
+        assert_eq!("content", json["changed_files"][0]["changes"][2]);
+
+        assert!(diff(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn line_diff() {
+        assert_eq!(vec!["+a"], diff_lines("", "a"));
+        assert_eq!(vec!["-a", " b", "+c"], diff_lines("a\nb", "b\nc"));
+    }
+}
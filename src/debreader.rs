@@ -0,0 +1,367 @@
+use crate::compress::{self, Format};
+use crate::error::*;
+use serde::Serialize;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+use tar::EntryType;
+
+/// Contents of a `.deb` file, as written by `DebArchive`, `dpkg-deb` or other tools
+#[derive(Debug, Clone, Serialize)]
+pub struct DebPackage {
+    /// Contents of the `debian-binary` member, normally `2.0`
+    pub format_version: String,
+    pub control_compression: Format,
+    pub data_compression: Format,
+    /// Fields of the `control` file, in their original order
+    pub control: ControlFields,
+    /// All files of the control archive, including `control`, `md5sums` and maintainer scripts
+    pub control_files: Vec<ControlFile>,
+    /// Files that get installed, in archive order
+    pub files: Vec<DataFile>,
+}
+
+/// `Name: value` pairs of the `control` file. Multi-line values keep their continuation lines as-is.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ControlFields(pub Vec<(String, String)>);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ControlFile {
+    pub name: String,
+    #[serde(serialize_with = "octal")]
+    pub mode: u32,
+    pub size: u64,
+    /// Converted lossily, since all control files are text
+    pub content: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DataFile {
+    /// Relative to the root directory, without the `./` prefix or the trailing `/` of directories
+    pub path: String,
+    pub kind: FileKind,
+    #[serde(serialize_with = "octal")]
+    pub mode: u32,
+    pub size: u64,
+    /// `user:group`, as names if the archive has them, or numeric IDs
+    pub owner: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link_target: Option<String>,
+    /// Only for regular files
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub md5: Option<String>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileKind {
+    File,
+    Dir,
+    Symlink,
+    Hardlink,
+    Other,
+}
+
+impl DebPackage {
+    pub fn read_file(path: &Path) -> CDResult<Self> {
+        let file = File::open(path)
+            .map_err(|e| CargoDebError::IoFile("unable to open the package", e, path.to_owned()))?;
+        Self::read(BufReader::new(file))
+    }
+
+    /// Parses the `ar` container and decompresses its `control.tar` and `data.tar` members
+    pub fn read(reader: impl Read) -> CDResult<Self> {
+        let mut ar = ar::Archive::new(reader);
+        let mut format_version = None;
+        let mut control = None;
+        let mut data = None;
+        while let Some(entry) = ar.next_entry() {
+            let mut entry = entry?;
+            let name = String::from_utf8_lossy(entry.header().identifier()).into_owned();
+            if name == "debian-binary" {
+                let mut version = String::new();
+                entry.read_to_string(&mut version)?;
+                format_version = Some(version.trim().to_owned());
+            } else if let Some(format) = Format::from_tar_file_name(&name, "control") {
+                control = Some((format, read_control_archive(compress::decompress(format, &mut entry)?)?));
+            } else if let Some(format) = Format::from_tar_file_name(&name, "data") {
+                data = Some((format, read_data_archive(compress::decompress(format, &mut entry)?)?));
+            } else if name.starts_with("control.") || name.starts_with("data.") {
+                return Err(CargoDebError::Str("unsupported compression of an archive in the package"));
+            }
+        }
+
+        let format_version = format_version.ok_or("not a Debian package: debian-binary is missing")?;
+        let (control_compression, control_files) = control.ok_or("not a Debian package: control.tar is missing")?;
+        let (data_compression, files) = data.ok_or("not a Debian package: data.tar is missing")?;
+        let control = control_files.iter().find(|f| f.name == "control")
+            .map(|f| ControlFields::parse(&f.content))
+            .ok_or("not a Debian package: control file is missing")?;
+
+        Ok(Self {
+            format_version,
+            control_compression,
+            data_compression,
+            control,
+            control_files,
+            files,
+        })
+    }
+
+    /// Value of a `control` field. Field names are case-insensitive.
+    #[must_use]
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.control.get(name)
+    }
+
+    #[must_use]
+    pub fn control_file(&self, name: &str) -> Option<&ControlFile> {
+        self.control_files.iter().find(|f| f.name == name)
+    }
+
+    /// Sum of sizes of regular files
+    #[must_use]
+    pub fn data_size(&self) -> u64 {
+        self.files.iter().filter(|f| f.kind == FileKind::File).map(|f| f.size).sum()
+    }
+}
+
+impl ControlFields {
+    #[must_use]
+    pub fn parse(control: &str) -> Self {
+        let mut fields: Vec<(String, String)> = Vec::new();
+        for line in control.lines() {
+            if line.starts_with([' ', '\t']) {
+                if let Some((_, value)) = fields.last_mut() {
+                    value.push('\n');
+                    value.push_str(line);
+                }
+            } else if let Some((name, value)) = line.split_once(':') {
+                fields.push((name.trim().to_owned(), value.trim().to_owned()));
+            }
+        }
+        Self(fields)
+    }
+
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+    }
+}
+
+impl Serialize for ControlFields {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|(n, v)| (n, v)))
+    }
+}
+
+/// Modes are more readable as `"0644"` than `420`
+fn octal<S: serde::Serializer>(mode: &u32, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&format_args!("{mode:04o}"))
+}
+
+fn read_control_archive(reader: impl Read) -> CDResult<Vec<ControlFile>> {
+    let mut files = Vec::new();
+    for entry in tar::Archive::new(reader).entries()? {
+        let mut entry = entry?;
+        if entry.header().entry_type() != EntryType::Regular {
+            continue;
+        }
+        let name = entry_path(&entry);
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content)?;
+        files.push(ControlFile {
+            name,
+            mode: entry.header().mode()? & 0o7777,
+            size: content.len() as u64,
+            content: String::from_utf8_lossy(&content).into_owned(),
+        });
+    }
+    Ok(files)
+}
+
+fn read_data_archive(reader: impl Read) -> CDResult<Vec<DataFile>> {
+    let mut files = Vec::new();
+    for entry in tar::Archive::new(reader).entries()? {
+        let mut entry = entry?;
+        let header = entry.header();
+        let kind = match header.entry_type() {
+            EntryType::Regular | EntryType::Continuous => FileKind::File,
+            EntryType::Directory => FileKind::Dir,
+            EntryType::Symlink => FileKind::Symlink,
+            EntryType::Link => FileKind::Hardlink,
+            _ => FileKind::Other,
+        };
+        let user = match header.username() {
+            Ok(Some(name)) if !name.is_empty() => name.to_owned(),
+            _ => header.uid()?.to_string(),
+        };
+        let group = match header.groupname() {
+            Ok(Some(name)) if !name.is_empty() => name.to_owned(),
+            _ => header.gid()?.to_string(),
+        };
+        let mode = header.mode()? & 0o7777;
+        let size = header.size()?;
+        let link_target = entry.link_name()?.map(|l| l.to_string_lossy().into_owned());
+        let path = entry_path(&entry);
+        let md5 = if kind == FileKind::File {
+            let mut hash = md5::Context::new();
+            io::copy(&mut entry, &mut hash)?;
+            Some(format!("{:x}", hash.compute()))
+        } else {
+            None
+        };
+        files.push(DataFile {
+            path,
+            kind,
+            mode,
+            size,
+            owner: format!("{user}:{group}"),
+            link_target,
+            md5,
+        });
+    }
+    Ok(files)
+}
+
+fn entry_path<R: Read>(entry: &tar::Entry<'_, R>) -> String {
+    let path = entry.path_bytes();
+    let path = String::from_utf8_lossy(&path);
+    let path = path.strip_prefix("./").unwrap_or(&path);
+    let path = path.strip_prefix('/').unwrap_or(path);
+    path.strip_suffix('/').unwrap_or(path).to_owned()
+}
+
+/// `ls -l`-style permissions, e.g. `drwxr-xr-x`
+#[must_use]
+pub fn mode_string(kind: FileKind, mode: u32) -> String {
+    let mut out = String::with_capacity(10);
+    out.push(match kind {
+        FileKind::Dir => 'd',
+        FileKind::Symlink => 'l',
+        FileKind::Hardlink => 'h',
+        FileKind::File => '-',
+        FileKind::Other => '?',
+    });
+    let special = [(0o4000, 's', 'S'), (0o2000, 's', 'S'), (0o1000, 't', 'T')];
+    for (i, (special_bit, exec_char, noexec_char)) in special.into_iter().enumerate() {
+        let bits = mode >> (6 - i * 3);
+        out.push(if bits & 4 != 0 { 'r' } else { '-' });
+        out.push(if bits & 2 != 0 { 'w' } else { '-' });
+        out.push(match (bits & 1 != 0, mode & special_bit != 0) {
+            (true, true) => exec_char,
+            (false, true) => noexec_char,
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    out
+}
+
+/// Similar to `dpkg-deb --info` followed by `dpkg-deb --contents`
+impl fmt::Display for DebPackage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, value) in &self.control.0 {
+            writeln!(f, "{name}: {value}")?;
+        }
+        writeln!(f)?;
+        writeln!(f, "Control archive ({}, format {}):", self.control_compression, self.format_version)?;
+        for file in &self.control_files {
+            writeln!(f, "  {} {:>10}  {}", mode_string(FileKind::File, file.mode), file.size, file.name)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "Data archive ({}, {} bytes in {} entries):", self.data_compression, self.data_size(), self.files.len())?;
+        for file in &self.files {
+            let slash = if file.kind == FileKind::Dir && !file.path.is_empty() { "/" } else { "" };
+            write!(f, "  {} {:<17} {:>10}  /{}{slash}", mode_string(file.kind, file.mode), file.owner, file.size, file.path)?;
+            if let Some(target) = &file.link_target {
+                write!(f, " -> {target}")?;
+            }
+            if let Some(md5) = &file.md5 {
+                write!(f, "  {md5}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::tararchive::Archive;
+    use std::io::Write;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut enc = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        enc.write_all(data).unwrap();
+        enc.finish().unwrap()
+    }
+
+    /// Package with the given control file, maintainer scripts, files and a `usr/bin/link` symlink
+    pub(crate) fn test_deb(control: &str, scripts: &[(&str, &str)], files: &[(&str, &[u8], u32)]) -> Vec<u8> {
+        let mut control_tar = Archive::new(Vec::new(), 0);
+        control_tar.file("./control", control.as_bytes(), 0o644).unwrap();
+        for (name, script) in scripts {
+            control_tar.file(format!("./{name}"), script.as_bytes(), 0o755).unwrap();
+        }
+        let control_tar = control_tar.into_inner().unwrap();
+
+        let mut data_tar = Archive::new(Vec::new(), 0);
+        for (path, data, mode) in files {
+            data_tar.file(path, data, *mode).unwrap();
+        }
+        data_tar.symlink(Path::new("usr/bin/link"), Path::new("app"), None).unwrap();
+        let data_tar = data_tar.into_inner().unwrap();
+
+        let mut ar = ar::Builder::new(Vec::new());
+        let mut add = |name: &str, data: &[u8]| {
+            ar.append(&ar::Header::new(name.into(), data.len() as u64), data).unwrap();
+        };
+        add("debian-binary", b"2.0\n");
+        add("control.tar.gz", &gzip(&control_tar));
+        add("data.tar", &data_tar);
+        ar.into_inner().unwrap()
+    }
+
+    #[test]
+    fn read_deb() {
+        let deb = test_deb("Package: test\nVersion: 1.0\nDescription: short\n long\n .\n more\n", &[("postinst", "#!/bin/sh\n")], &[
+            ("usr/bin/app", b"hello\n", 0o4755),
+        ]);
+        let deb = DebPackage::read(&deb[..]).unwrap();
+        assert_eq!("2.0", deb.format_version);
+        assert_eq!(Format::Gz, deb.control_compression);
+        assert_eq!(Format::Uncompressed, deb.data_compression);
+        assert_eq!(Some("1.0"), deb.field("version"));
+        assert_eq!(Some("short\n long\n .\n more"), deb.field("Description"));
+        assert_eq!(0o755, deb.control_file("postinst").unwrap().mode);
+
+        let paths: Vec<_> = deb.files.iter().map(|f| (f.path.as_str(), f.kind)).collect();
+        assert_eq!(vec![("usr", FileKind::Dir), ("usr/bin", FileKind::Dir), ("usr/bin/app", FileKind::File), ("usr/bin/link", FileKind::Symlink)], paths);
+        let app = &deb.files[2];
+        assert_eq!((0o4755, 6, "root:root"), (app.mode, app.size, app.owner.as_str()));
+        assert_eq!(Some("b1946ac92492d2347c6235b4d2611184"), app.md5.as_deref());
+        assert_eq!(Some("app"), deb.files[3].link_target.as_deref());
+
+        let json = serde_json::to_value(&deb).unwrap();
+        assert_eq!("1.0", json["control"]["Version"]);
+        assert_eq!("symlink", json["files"][3]["kind"]);
+        assert_eq!("4755", json["files"][2]["mode"]);
+    }
+
+    #[test]
+    fn not_a_deb() {
+        assert!(DebPackage::read(&b"!<arch>\n"[..]).is_err());
+        assert!(DebPackage::read(&b"hello"[..]).is_err());
+    }
+
+    #[test]
+    fn modes() {
+        assert_eq!("drwxr-xr-x", mode_string(FileKind::Dir, 0o755));
+        assert_eq!("-rwsr-x---", mode_string(FileKind::File, 0o4750));
+        assert_eq!("-rw-r-Sr--", mode_string(FileKind::File, 0o2644));
+        assert_eq!("drwxrwxrwt", mode_string(FileKind::Dir, 0o1777));
+    }
+}
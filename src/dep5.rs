@@ -0,0 +1,191 @@
+//! `Files` and `License` paragraphs of machine-readable `copyright` files for crates linked into the package
+//! <https://www.debian.org/doc/packaging-manuals/copyright-format/1.0/>
+
+use crate::manifest::LinkedCrate;
+use crate::spdx::{common_license_reference, debian_license, LicenseExpr};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+
+/// Appends a `Files` paragraph for the package's own files (if `license` is given), and one for each crate,
+/// followed by standalone `License` paragraphs for every license they use.
+///
+/// Crates' `Files` patterns are the directories `cargo vendor --versioned-dirs` would put their sources in.
+pub(crate) fn append_linked_crates(copyright: &mut String, copyright_holder: &str, license: Option<&str>, crates: &[LinkedCrate]) {
+    let mut licenses = BTreeMap::new();
+
+    if let Some(license) = license {
+        let _ = write!(copyright, "\nFiles: *\nCopyright: {copyright_holder}\nLicense: {}\n", debian_license(license));
+        for id in license_names(license) {
+            licenses.entry(id).or_insert(None);
+        }
+    }
+
+    for c in crates {
+        let authors = if c.authors.is_empty() { format!("{} developers", c.name) } else { c.authors.join("\n ") };
+        let _ = write!(copyright, "\nFiles: vendor/{}-{}/*\nCopyright: {authors}\n", c.name, c.version);
+        let _ = writeln!(copyright, "License: {}", c.license.as_deref().map_or_else(|| "UNKNOWN".into(), debian_license));
+        let _ = write!(copyright, "Comment: Statically linked from the {} {} crate", c.name, c.version);
+        if let Some(repo) = &c.repository {
+            let _ = write!(copyright, " <{repo}>");
+        }
+        copyright.push('\n');
+
+        let ids = c.license.as_deref().map(license_names).unwrap_or_default();
+        let texts = license_texts(c, &ids);
+        for id in ids {
+            let text = licenses.entry(id.clone()).or_insert(None);
+            if text.is_none() {
+                *text = texts.get(&id).cloned();
+            }
+        }
+    }
+
+    for (id, text) in licenses {
+        let _ = write!(copyright, "\nLicense: {id}\n");
+        if let Some(reference) = common_license_reference(&id) {
+            copyright.push_str(&reference);
+        } else if let Some(text) = text {
+            for line in text.lines() {
+                let line = line.trim_end();
+                if line.is_empty() {
+                    copyright.push_str(" .\n");
+                } else {
+                    let _ = writeln!(copyright, " {line}");
+                }
+            }
+        } else {
+            copyright.push_str(" The full text of this license was not found in the crate's sources.\n");
+        }
+    }
+}
+
+/// Appends standalone `License` paragraphs referring to `/usr/share/common-licenses` for the licenses that are there
+pub(crate) fn append_common_licenses(copyright: &mut String, license: &str) {
+    for name in license_names(license) {
+        if let Some(reference) = common_license_reference(&name) {
+            let _ = write!(copyright, "\nLicense: {name}\n{reference}");
+        }
+    }
+}
+
+/// Debian names of licenses in an expression, without operators and exceptions
+fn license_names(license: &str) -> Vec<String> {
+    match LicenseExpr::parse(license) {
+        Ok(parsed) => parsed.debian_names(),
+        Err(_) => vec![license.to_owned()],
+    }
+}
+
+/// The crate's `license-file`, and `LICENSE*`, `COPYING*` and `NOTICE*` files in its root directory
+pub(crate) fn license_files(c: &LinkedCrate) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = c.license_file.iter().filter(|f| f.is_file()).cloned().collect();
+    if let Ok(dir) = fs::read_dir(&c.source_dir) {
+        let mut found: Vec<_> = dir.filter_map(|e| e.ok()).map(|e| e.path())
+            .filter(|p| p.is_file() && !files.contains(p) && p.file_name().and_then(|n| n.to_str()).map_or(false, |n| {
+                let n = n.to_ascii_uppercase();
+                ["LICENSE", "LICENCE", "COPYING", "UNLICENSE", "NOTICE"].iter().any(|prefix| n.starts_with(prefix))
+            }))
+            .collect();
+        found.sort();
+        files.extend(found);
+    }
+    files
+}
+
+/// Reads the crate's license files, and guesses which of its licenses they contain
+fn license_texts(c: &LinkedCrate, ids: &[String]) -> BTreeMap<String, String> {
+    let mut texts = BTreeMap::new();
+    for path in license_files(c) {
+        if path.file_name().and_then(|n| n.to_str()).map_or(false, |n| n.to_ascii_uppercase().starts_with("NOTICE")) {
+            continue;
+        }
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(_) => continue,
+        };
+        let name = path.file_stem().and_then(|n| n.to_str()).unwrap_or_default().to_ascii_lowercase();
+        // LICENSE-MIT, LICENSE-APACHE, COPYING.LGPL, etc.
+        let suffix = match name.as_str() {
+            "unlicense" => "unlicense",
+            _ => name.trim_start_matches(|c: char| c.is_ascii_alphabetic()).trim_start_matches(['-', '_', '.']),
+        };
+        let suffix = if suffix == "boost" { "bsl" } else { suffix };
+        let id = if !suffix.is_empty() {
+            ids.iter().find(|id| id.to_ascii_lowercase().starts_with(suffix))
+        } else if ids.len() == 1 {
+            ids.first()
+        } else {
+            // only when it's unambiguous, since files like COPYING often just list the options
+            let mut mentioned = ids.iter().filter(|id| text.contains(id.as_str()));
+            mentioned.next().filter(|_| mentioned.next().is_none())
+        };
+        if let Some(id) = id {
+            texts.entry(id.clone()).or_insert(text);
+        }
+    }
+    texts
+}
+
+#[test]
+fn linked_crates_paragraphs() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("LICENSE-MIT"), "Permission is hereby granted\n\nTHE SOFTWARE IS PROVIDED \"AS IS\"\n").unwrap();
+    fs::write(dir.path().join("LICENSE-APACHE"), "Apache License\n").unwrap();
+    let crates = [LinkedCrate {
+        name: "foo".into(),
+        version: "1.0.0".into(),
+        license: Some("MIT/Apache-2.0".into()),
+        license_file: None,
+        authors: vec!["A <a@example.com>".into(), "B".into()],
+        repository: Some("https://example.com/foo".into()),
+        source_dir: dir.path().to_owned(),
+    }, LinkedCrate {
+        name: "bar".into(),
+        version: "0.1.0".into(),
+        license: Some("(Zlib OR GPL-3.0-or-later) AND Apache-2.0 WITH LLVM-exception".into()),
+        license_file: None,
+        authors: vec![],
+        repository: None,
+        source_dir: dir.path().join("missing"),
+    }];
+    let mut out = String::new();
+    append_linked_crates(&mut out, "Me", None, &crates[1..]);
+    assert!(out.starts_with("\nFiles: vendor/bar-0.1.0/*\n"));
+
+    let mut out = String::new();
+    append_linked_crates(&mut out, "Me", Some("MIT"), &crates);
+    assert_eq!(out, "
+Files: *
+Copyright: Me
+License: MIT
+
+Files: vendor/foo-1.0.0/*
+Copyright: A <a@example.com>
+ B
+License: MIT or Apache-2.0
+Comment: Statically linked from the foo 1.0.0 crate <https://example.com/foo>
+
+Files: vendor/bar-0.1.0/*
+Copyright: bar developers
+License: Zlib or GPL-3+, and Apache-2.0 with LLVM exception
+Comment: Statically linked from the bar 0.1.0 crate
+
+License: Apache-2.0
+ On Debian systems, the complete text of the Apache-2.0 license can be found in
+ /usr/share/common-licenses/Apache-2.0.
+
+License: GPL-3+
+ On Debian systems, the complete text of the GPL-3+ license can be found in
+ /usr/share/common-licenses/GPL-3.
+
+License: MIT
+ Permission is hereby granted
+ .
+ THE SOFTWARE IS PROVIDED \"AS IS\"
+
+License: Zlib
+ The full text of this license was not found in the crate's sources.
+");
+}
@@ -0,0 +1,103 @@
+use crate::error::*;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Remembers `$auto` dependencies of binaries between runs.
+///
+/// Entries are keyed by a hash of the binary's contents, the target, the files in the package, and the state of the dpkg database
+/// the dependencies were looked up in, so a change to any of them makes a new entry.
+pub(crate) struct DependencyCache {
+    dir: PathBuf,
+    /// Hash state of everything except the binary
+    context: md5::Context,
+}
+
+impl DependencyCache {
+    pub fn new(dir: PathBuf, target: Option<&str>, architecture: &str, sysroot: Option<&Path>, package_files: &HashSet<&str>) -> Self {
+        let mut context = md5::Context::new();
+        let root = sysroot.unwrap_or(Path::new("/"));
+        context.consume(env!("CARGO_PKG_VERSION"));
+        context.consume([0]);
+        context.consume(target.unwrap_or(""));
+        context.consume([0]);
+        context.consume(architecture);
+        context.consume([0]);
+        context.consume(root.as_os_str().to_string_lossy().as_bytes());
+        context.consume([0]);
+        // Libraries in the package itself are not dependencies
+        let mut package_files: Vec<_> = package_files.iter().collect();
+        package_files.sort_unstable();
+        for file in package_files {
+            context.consume(file);
+            context.consume([0]);
+        }
+        context.consume([0]);
+        // Without the dpkg database, dependencies come from dpkg-shlibdeps, which may find different ones
+        let info_dir = root.join("var/lib/dpkg/info");
+        context.consume(if info_dir.is_dir() { "native" } else { "dpkg-shlibdeps" });
+        context.consume([0]);
+        // Installing or upgrading packages rewrites the status file, and adds or removes files in info/
+        for db_path in [root.join("var/lib/dpkg/status"), info_dir] {
+            if let Ok(meta) = fs::metadata(&db_path) {
+                let mtime = meta.modified().ok()
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map_or(0, |t| t.as_nanos());
+                context.consume(mtime.to_le_bytes());
+                context.consume(meta.len().to_le_bytes());
+            }
+        }
+        Self { dir, context }
+    }
+
+    /// Hash of the binary together with the rest of the cache key
+    pub fn key(&self, binary_path: &Path) -> CDResult<String> {
+        let mut context = self.context.clone();
+        io::copy(&mut fs::File::open(binary_path)?, &mut context)?;
+        Ok(format!("{:x}", context.compute()))
+    }
+
+    pub fn get(&self, key: &str) -> Option<Vec<String>> {
+        let cached = fs::read_to_string(self.dir.join(key)).ok()?;
+        Some(cached.lines().map(|dep| dep.to_owned()).collect())
+    }
+
+    /// Failures are only logged, since the cache is not essential
+    pub fn insert(&self, key: &str, deps: &[String]) {
+        let res = fs::create_dir_all(&self.dir).and_then(|_| {
+            // Renamed into place, so that parallel builds never see a partially-written entry
+            let tmp_path = self.dir.join(format!("{key}.{}.tmp", std::process::id()));
+            fs::write(&tmp_path, deps.join("\n"))?;
+            fs::rename(&tmp_path, self.dir.join(key))
+        });
+        if let Err(err) = res {
+            log::debug!("can't cache dependencies in {}: {err}", self.dir.display());
+        }
+    }
+}
+
+#[test]
+fn dependency_cache() {
+    let dir = tempfile::tempdir().unwrap();
+    let files = HashSet::new();
+    let cache = DependencyCache::new(dir.path().join("cache"), Some("aarch64-unknown-linux-gnu"), "arm64", None, &files);
+    let exe = std::env::current_exe().unwrap();
+    let key = cache.key(&exe).unwrap();
+    assert_eq!(None, cache.get(&key));
+    cache.insert(&key, &["libc6 (>= 2.34)".into(), "libfoo1".into()]);
+    assert_eq!(Some(vec!["libc6 (>= 2.34)".to_string(), "libfoo1".into()]), cache.get(&key));
+
+    let other_target = DependencyCache::new(dir.path().join("cache"), None, "amd64", None, &files);
+    assert_ne!(key, other_target.key(&exe).unwrap());
+
+    let with_lib = DependencyCache::new(dir.path().join("cache"), Some("aarch64-unknown-linux-gnu"), "arm64", None, &["libfoo.so.1"].into_iter().collect());
+    assert_ne!(key, with_lib.key(&exe).unwrap());
+
+    let sysroot = tempfile::tempdir().unwrap();
+    let without_db = DependencyCache::new(dir.path().join("cache"), Some("aarch64-unknown-linux-gnu"), "arm64", Some(sysroot.path()), &files);
+    fs::create_dir_all(sysroot.path().join("var/lib/dpkg/info")).unwrap();
+    let with_db = DependencyCache::new(dir.path().join("cache"), Some("aarch64-unknown-linux-gnu"), "arm64", Some(sysroot.path()), &files);
+    assert_ne!(without_db.key(&exe).unwrap(), with_db.key(&exe).unwrap());
+}
@@ -1,11 +1,35 @@
 use crate::debian_triple;
+use crate::elf;
 use crate::error::*;
+use crate::relations::{RelationField, RelationList};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fs;
 use std::io::BufRead;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
+/// Directories with shared libraries of the target system, in Debian's multiarch layout (`/usr/lib/aarch64-linux-gnu`),
+/// the old cross-compilation layout (`/usr/aarch64-linux-gnu/lib`), or the plain `/usr/lib` of a sysroot.
+pub(crate) fn library_dirs(target: Option<&str>, sysroot: Option<&Path>) -> Vec<PathBuf> {
+    let multiarch = debian_triple(target.unwrap_or(crate::DEFAULT_TARGET));
+    let root = sysroot.unwrap_or(Path::new("/"));
+    let mut dirs = vec![
+        root.join("lib").join(&multiarch),
+        root.join("usr/lib").join(&multiarch),
+    ];
+    if sysroot.is_some() {
+        dirs.push(root.join("lib"));
+        dirs.push(root.join("usr/lib"));
+    } else {
+        dirs.push(root.join("usr").join(&multiarch).join("lib"));
+    }
+    dirs.retain(|dir| dir.exists());
+    dirs
+}
+
 /// Resolves the dependencies based on the output of dpkg-shlibdeps on the binary.
-pub fn resolve(path: &Path, target: &Option<String>) -> CDResult<Vec<String>> {
+pub fn resolve(path: &Path, target: &Option<String>, sysroot: Option<&Path>) -> CDResult<Vec<String>> {
     let temp_folder = tempfile::tempdir()?;
     let debian_folder = temp_folder.path().join("debian");
     let control_file_path = debian_folder.join("control");
@@ -20,9 +44,11 @@ pub fn resolve(path: &Path, target: &Option<String>) -> CDResult<Vec<String>> {
     // Print result to stdout instead of a file.
     let mut args = Vec::from([String::from("-O")]);
     // determine library search path from target
-    if let Some(target) = target {
-        let libpath_arg = format!("-l/usr/{}/lib", debian_triple(target));
-        args.push(libpath_arg);
+    if target.is_some() || sysroot.is_some() {
+        args.extend(library_dirs(target.as_deref(), sysroot).iter().map(|dir| format!("-l{}", dir.display())));
+    }
+    if let Some(admindir) = sysroot.map(|s| s.join("var/lib/dpkg")).filter(|dir| dir.exists()) {
+        args.push(format!("--admindir={}", admindir.display()));
     }
     const DPKG_SHLIBDEPS_COMMAND: &str = "dpkg-shlibdeps";
     let output = Command::new(DPKG_SHLIBDEPS_COMMAND)
@@ -55,11 +81,443 @@ pub fn resolve(path: &Path, target: &Option<String>) -> CDResult<Vec<String>> {
     Ok(deps)
 }
 
+/// Shared libraries' dependency information from the dpkg database (`/var/lib/dpkg/info/*.symbols` and `*.shlibs`),
+/// used to compute dependencies the same way as `dpkg-shlibdeps`, but without needing it to be installed.
+#[derive(Debug, Default)]
+pub(crate) struct ShlibsDatabase {
+    /// By soname
+    symbols: HashMap<String, SymbolsEntry>,
+    /// By (library name, soversion)
+    shlibs: HashMap<(String, String), String>,
+}
+
+/// Section of a `.symbols` file for one soname
+#[derive(Debug, Default)]
+struct SymbolsEntry {
+    /// Main dependency template, e.g. `libc6 #MINVER#`, followed by alternative dependencies from `|` lines
+    dependencies: Vec<String>,
+    /// `name@version` -> (minimum version, index of the dependency template)
+    symbols: HashMap<String, (String, usize)>,
+}
+
+/// The first architecture in the dpkg database is the native one. Asks `dpkg` if that's missing for the host system.
+fn native_dpkg_arch(root: &Path) -> Option<String> {
+    if let Ok(archs) = fs::read_to_string(root.join("var/lib/dpkg/arch")) {
+        if let Some(native) = archs.lines().map(|l| l.trim()).find(|l| !l.is_empty()) {
+            return Some(native.to_owned());
+        }
+    }
+    if root != Path::new("/") {
+        return None;
+    }
+    let output = Command::new("dpkg").arg("--print-architecture").output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok().map(|s| s.trim().to_owned()).filter(|s| !s.is_empty())
+}
+
+impl ShlibsDatabase {
+    /// Reads the dpkg database in `root` (usually `/`) for packages of the given Debian architecture.
+    /// Fails if there's no dpkg database there.
+    pub fn load(root: &Path, arch: &str) -> CDResult<Self> {
+        let info_dir = root.join("var/lib/dpkg/info");
+        let mut db = Self::default();
+        let arch_suffix = format!(":{arch}");
+        // Files without arch are from the native architecture (or arch-independent), so they don't apply to a foreign one
+        let load_unsuffixed = native_dpkg_arch(root).map_or(true, |native| native == arch);
+        for entry in fs::read_dir(&info_dir).map_err(|e| CargoDebError::IoFile("unable to read dpkg database", e, info_dir.clone()))? {
+            let path = entry?.path();
+            let (package, ext) = match path.file_name().and_then(|f| f.to_str()).and_then(|f| f.rsplit_once('.')) {
+                Some(parts) => parts,
+                None => continue,
+            };
+            let matches_arch = if package.contains(':') { package.ends_with(&arch_suffix) } else { load_unsuffixed };
+            if !matches_arch {
+                continue;
+            }
+            match ext {
+                "symbols" => db.add_symbols_file(&fs::read_to_string(&path)?),
+                "shlibs" => db.add_shlibs_file(&fs::read_to_string(&path)?),
+                _ => {},
+            }
+        }
+        Ok(db)
+    }
+
+    fn add_symbols_file(&mut self, content: &str) {
+        let mut current = None;
+        for line in content.lines() {
+            if let Some(symbol) = line.strip_prefix(' ') {
+                let entry = match current.as_ref().and_then(|soname| self.symbols.get_mut(soname)) {
+                    Some(entry) => entry,
+                    None => continue,
+                };
+                // Optional tags like `(c++)"symbol@Base" 1.0`
+                let symbol = match symbol.strip_prefix('(') {
+                    Some(tagged) => tagged.split_once(')').map_or(tagged, |(_, rest)| rest),
+                    None => symbol,
+                };
+                let (name, rest) = if let Some(quoted) = symbol.strip_prefix('"') {
+                    quoted.split_once('"').unwrap_or((quoted, ""))
+                } else {
+                    symbol.split_once(' ').unwrap_or((symbol, ""))
+                };
+                let mut rest = rest.split_whitespace();
+                let minver = rest.next().unwrap_or("").to_owned();
+                let dep_index = rest.next().and_then(|i| i.parse().ok()).unwrap_or(0);
+                entry.symbols.insert(name.to_owned(), (minver, dep_index));
+            } else if let Some(alternative) = line.strip_prefix('|') {
+                if let Some(entry) = current.as_ref().and_then(|soname| self.symbols.get_mut(soname)) {
+                    entry.dependencies.push(alternative.trim().to_owned());
+                }
+            } else if line.starts_with('*') || line.starts_with('#') || line.trim().is_empty() {
+                continue;
+            } else if let Some((soname, dependency)) = line.split_once(' ') {
+                self.symbols.insert(soname.to_owned(), SymbolsEntry {
+                    dependencies: vec![dependency.trim().to_owned()],
+                    symbols: HashMap::new(),
+                });
+                current = Some(soname.to_owned());
+            }
+        }
+    }
+
+    fn add_shlibs_file(&mut self, content: &str) {
+        for line in content.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+            let mut parts = line.splitn(3, ' ');
+            let (name, version, dependency) = match (parts.next(), parts.next(), parts.next()) {
+                (Some(name), Some(version), Some(dependency)) => (name, version, dependency),
+                _ => continue,
+            };
+            // Only the default type, not `udeb:` ones
+            if name.ends_with(':') {
+                continue;
+            }
+            self.shlibs.insert((name.to_owned(), version.to_owned()), dependency.trim().to_owned());
+        }
+    }
+
+    /// Dependencies of the ELF binary at `path`, in the same format as [`resolve`],
+    /// and sonames that aren't in the database.
+    ///
+    /// Libraries in `package_files` (file names) are installed by the package itself, and don't need a dependency.
+    pub fn resolve(&self, path: &Path, package_files: &HashSet<&str>) -> CDResult<(Vec<String>, Vec<String>)> {
+        let info = match elf::dynamic_info(path)? {
+            Some(info) => info,
+            None => return Ok((Vec::new(), Vec::new())),
+        };
+        let mut deps = Vec::new();
+        let mut missing = Vec::new();
+        for soname in &info.needed {
+            if package_files.contains(soname.as_str()) {
+                continue;
+            }
+            if let Some(entry) = self.symbols.get(soname) {
+                // The highest version of any symbol used is the minimum version required
+                let mut min_versions: Vec<Option<&str>> = vec![None; entry.dependencies.len()];
+                let mut used = vec![false; entry.dependencies.len()];
+                used[0] = true;
+                for import in &info.imports {
+                    let key = match &import.version {
+                        Some((version, file)) if file == soname => format!("{}@{version}", import.name),
+                        Some(_) => continue,
+                        None => format!("{}@Base", import.name),
+                    };
+                    if let Some((minver, dep_index)) = entry.symbols.get(&key) {
+                        let dep_index = (*dep_index).min(entry.dependencies.len() - 1);
+                        used[dep_index] = true;
+                        let min = &mut min_versions[dep_index];
+                        if min.map_or(true, |min| compare_versions(minver, min) == Ordering::Greater) {
+                            *min = Some(minver);
+                        }
+                    }
+                }
+                for ((template, min), _) in entry.dependencies.iter().zip(min_versions).zip(used).filter(|(_, used)| *used) {
+                    let minver = match min.filter(|v| !v.is_empty() && *v != "0") {
+                        Some(v) => format!(" (>= {v})"),
+                        None => String::new(),
+                    };
+                    deps.extend(template.replace(" #MINVER#", &minver).split(',').map(|d| d.trim().to_owned()));
+                }
+            } else if let Some(dependency) = split_soname(soname).and_then(|key| self.shlibs.get(&key)) {
+                deps.extend(dependency.split(',').map(|d| d.trim().to_owned()));
+            } else {
+                missing.push(soname.clone());
+            }
+        }
+        Ok((merge_dependencies(deps)?, missing))
+    }
+}
+
+/// Highest versions of glibc (`GLIBC_x.y`) and libgcc_s (`GCC_x.y.z`) symbols that a binary uses.
+/// These are the oldest library versions the binary can run with.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct SymbolVersionRequirements {
+    pub glibc: Option<String>,
+    pub gcc: Option<String>,
+}
+
+impl SymbolVersionRequirements {
+    pub fn of_binary(path: &Path) -> CDResult<Self> {
+        let mut req = Self::default();
+        if let Some(info) = elf::dynamic_info(path)? {
+            for (_, version) in &info.version_needs {
+                if let Some(v) = version.strip_prefix("GLIBC_") {
+                    raise_version(&mut req.glibc, v);
+                } else if let Some(v) = version.strip_prefix("GCC_") {
+                    raise_version(&mut req.gcc, v);
+                }
+            }
+        }
+        Ok(req)
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        if let Some(v) = &other.glibc {
+            raise_version(&mut self.glibc, v);
+        }
+        if let Some(v) = &other.gcc {
+            raise_version(&mut self.gcc, v);
+        }
+    }
+
+    /// Descriptions of requirements that the baseline doesn't meet
+    pub fn exceeding(&self, baseline: &Baseline) -> Vec<String> {
+        let check = |lib: &str, required: &Option<String>, available: &Option<String>| {
+            match (required, available) {
+                (Some(required), Some(available)) if compare_versions(required, available) == Ordering::Greater => {
+                    Some(format!("{lib} {required}, but {} has {available}", baseline.name))
+                },
+                _ => None,
+            }
+        };
+        check("glibc", &self.glibc, &baseline.glibc).into_iter()
+            .chain(check("libgcc", &self.gcc, &baseline.gcc))
+            .collect()
+    }
+}
+
+/// Keeps the higher version. Ignores non-numeric versions like `GLIBC_PRIVATE`.
+fn raise_version(current: &mut Option<String>, version: &str) {
+    if !version.starts_with(|c: char| c.is_ascii_digit()) {
+        return;
+    }
+    if current.as_deref().map_or(true, |current| compare_versions(version, current) == Ordering::Greater) {
+        *current = Some(version.to_owned());
+    }
+}
+
+/// glibc and GCC versions of a distro release that the package is meant to support
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Baseline {
+    pub name: String,
+    pub glibc: Option<String>,
+    pub gcc: Option<String>,
+}
+
+/// (name, glibc, libgcc) of releases that `baseline` can refer to by name
+const DISTRO_BASELINES: &[(&str, &str, &str)] = &[
+    ("debian-10", "2.28", "8.3"),
+    ("debian-11", "2.31", "10.2"),
+    ("debian-12", "2.36", "12.2"),
+    ("debian-13", "2.41", "14.2"),
+    ("ubuntu-18.04", "2.27", "8.4"),
+    ("ubuntu-20.04", "2.31", "10.5"),
+    ("ubuntu-22.04", "2.35", "12.3"),
+    ("ubuntu-24.04", "2.39", "14.2"),
+];
+
+impl Baseline {
+    pub fn distro(name: &str) -> CDResult<Self> {
+        DISTRO_BASELINES.iter().find(|(n, ..)| *n == name)
+            .map(|&(name, glibc, gcc)| Self { name: name.into(), glibc: Some(glibc.into()), gcc: Some(gcc.into()) })
+            .ok_or_else(|| CargoDebError::UnknownBaseline(name.into(), DISTRO_BASELINES.iter().map(|(n, ..)| *n).collect::<Vec<_>>().join(", ")))
+    }
+}
+
+/// `libfoo.so.1` -> (`libfoo`, `1`), and `libfoo-1.2.so` -> (`libfoo`, `1.2`), as in shlibs files
+fn split_soname(soname: &str) -> Option<(String, String)> {
+    if let Some((name, version)) = soname.split_once(".so.") {
+        return Some((name.to_owned(), version.to_owned()));
+    }
+    let (name, version) = soname.strip_suffix(".so")?.rsplit_once('-')?;
+    if !version.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    Some((name.to_owned(), version.to_owned()))
+}
+
+/// Deduplicates `pkg (>= ver)` dependencies, keeping the highest version of each package, sorted like `dpkg-shlibdeps` does
+pub(crate) fn merge_dependencies(deps: Vec<String>) -> CDResult<Vec<String>> {
+    let mut list = RelationList::default();
+    for dep in deps.iter()
+        // libgcc guaranteed by LSB to always be present
+        .filter(|dep| !dep.starts_with("libgcc-") && !dep.starts_with("libgcc1")) {
+        list.extend(RelationList::parse(RelationField::Depends, dep)?);
+    }
+    list.normalize(RelationField::Depends);
+    let mut deps: Vec<_> = list.dependencies.iter().map(|dep| dep.to_string()).collect();
+    deps.sort();
+    Ok(deps)
+}
+
+/// Compares Debian package versions, like `dpkg --compare-versions`
+pub(crate) fn compare_versions(a: &str, b: &str) -> Ordering {
+    fn split(v: &str) -> (u64, &str, &str) {
+        let (epoch, rest) = match v.split_once(':') {
+            Some((epoch, rest)) => (epoch.parse().unwrap_or(0), rest),
+            None => (0, v),
+        };
+        let (upstream, revision) = rest.rsplit_once('-').unwrap_or((rest, ""));
+        (epoch, upstream, revision)
+    }
+    let (a_epoch, a_upstream, a_revision) = split(a);
+    let (b_epoch, b_upstream, b_revision) = split(b);
+    a_epoch.cmp(&b_epoch)
+        .then_with(|| compare_version_part(a_upstream, b_upstream))
+        .then_with(|| compare_version_part(a_revision, b_revision))
+}
+
+/// dpkg's `verrevcmp`: non-digit runs compare with `~` sorting before everything (even the end),
+/// and letters before other characters; digit runs compare numerically
+fn compare_version_part(a: &str, b: &str) -> Ordering {
+    fn order(c: Option<u8>) -> i32 {
+        match c {
+            Some(b'~') => -1,
+            None => 0,
+            Some(c) if c.is_ascii_digit() => 0,
+            Some(c) if c.is_ascii_alphabetic() => i32::from(c),
+            Some(c) => i32::from(c) + 256,
+        }
+    }
+    let (mut a, mut b) = (a.as_bytes(), b.as_bytes());
+    while !a.is_empty() || !b.is_empty() {
+        while a.first().map_or(false, |c| !c.is_ascii_digit()) || b.first().map_or(false, |c| !c.is_ascii_digit()) {
+            let (ac, bc) = (order(a.first().copied()), order(b.first().copied()));
+            if ac != bc {
+                return ac.cmp(&bc);
+            }
+            a = &a[1.min(a.len())..];
+            b = &b[1.min(b.len())..];
+        }
+        let a_digits = a.iter().take_while(|c| c.is_ascii_digit()).count();
+        let b_digits = b.iter().take_while(|c| c.is_ascii_digit()).count();
+        let (a_num, b_num) = (trim_zeros(&a[..a_digits]), trim_zeros(&b[..b_digits]));
+        let ord = a_num.len().cmp(&b_num.len()).then_with(|| a_num.cmp(b_num));
+        if ord != Ordering::Equal {
+            return ord;
+        }
+        a = &a[a_digits..];
+        b = &b[b_digits..];
+    }
+    Ordering::Equal
+}
+
+fn trim_zeros(digits: &[u8]) -> &[u8] {
+    &digits[digits.iter().take_while(|&&c| c == b'0').count()..]
+}
+
+#[test]
+fn version_comparison() {
+    assert_eq!(Ordering::Greater, compare_versions("2.34", "2.2.5"));
+    assert_eq!(Ordering::Less, compare_versions("1.0~rc1", "1.0"));
+    assert_eq!(Ordering::Greater, compare_versions("1:0.1", "2.0"));
+    assert_eq!(Ordering::Equal, compare_versions("1.01", "1.1"));
+    assert_eq!(Ordering::Less, compare_versions("1.0-1", "1.0-2"));
+    assert_eq!(Ordering::Greater, compare_versions("1.0a", "1.0"));
+    assert_eq!(Ordering::Less, compare_versions("1.0", "1.0+b1"));
+}
+
+#[test]
+fn shlibs_database() {
+    let mut db = ShlibsDatabase::default();
+    db.add_symbols_file("libc.so.6 libc6 #MINVER#\n| libc6 (>> 2.36), libc6 (<< 2.37)\n* Build-Depends-Package: libc-dev\n GLIBC_2.2.5@GLIBC_2.2.5 2.2.5\n malloc@GLIBC_2.2.5 2.2.5\n getrandom@GLIBC_2.25 2.25\n __private@GLIBC_PRIVATE 0 1\n");
+    db.add_shlibs_file("libz 1 zlib1g (>= 1:1.2.3)\nudeb: libz 1 zlib1g-udeb\n");
+    let entry = &db.symbols["libc.so.6"];
+    assert_eq!(("2.25".to_string(), 0), entry.symbols["getrandom@GLIBC_2.25"]);
+    assert_eq!(("0".to_string(), 1), entry.symbols["__private@GLIBC_PRIVATE"]);
+    assert_eq!(2, entry.dependencies.len());
+    assert_eq!("zlib1g (>= 1:1.2.3)", db.shlibs[&("libz".to_string(), "1".to_string())]);
+    assert_eq!(Some(("libfoo".into(), "1.2".into())), split_soname("libfoo-1.2.so"));
+    assert_eq!(Some(("libc".into(), "6".into())), split_soname("libc.so.6"));
+
+    assert_eq!(vec!["libc6 (>= 2.34)", "zlib1g"], merge_dependencies(vec![
+        "zlib1g".into(), "libc6 (>= 2.2.5)".into(), "libc6 (>= 2.34)".into(), "libc6".into(), "libgcc-s1 (>= 4.2)".into(),
+    ]).unwrap());
+    assert!(merge_dependencies(vec!["zlib1g".into(), "libc6 (>= ".into()]).is_err());
+}
+
+#[test]
+fn shlibs_database_foreign_arch() {
+    let root = tempfile::tempdir().unwrap();
+    let info = root.path().join("var/lib/dpkg/info");
+    fs::create_dir_all(&info).unwrap();
+    fs::write(root.path().join("var/lib/dpkg/arch"), "amd64\narm64\n").unwrap();
+    fs::write(info.join("zlib1g.shlibs"), "libz 1 zlib1g (>= 1:1.2.3)\n").unwrap();
+    fs::write(info.join("zlib1g:arm64.shlibs"), "libz 1 zlib1g:arm64 (>= 1:1.2.3)\n").unwrap();
+    let key = ("libz".to_string(), "1".to_string());
+    assert_eq!("zlib1g (>= 1:1.2.3)", ShlibsDatabase::load(root.path(), "amd64").unwrap().shlibs[&key]);
+    assert_eq!("zlib1g:arm64 (>= 1:1.2.3)", ShlibsDatabase::load(root.path(), "arm64").unwrap().shlibs[&key]);
+    assert!(ShlibsDatabase::load(root.path(), "armhf").unwrap().shlibs.is_empty());
+}
+
+#[test]
+fn symbol_version_baseline() {
+    let mut req = SymbolVersionRequirements::default();
+    raise_version(&mut req.glibc, "2.17");
+    raise_version(&mut req.glibc, "PRIVATE");
+    req.merge(&SymbolVersionRequirements { glibc: Some("2.34".into()), gcc: Some("4.2.0".into()) });
+    assert_eq!(Some("2.34"), req.glibc.as_deref());
+
+    assert_eq!(vec!["glibc 2.34, but debian-11 has 2.31"], req.exceeding(&Baseline::distro("debian-11").unwrap()));
+    assert!(req.exceeding(&Baseline::distro("ubuntu-22.04").unwrap()).is_empty());
+    let gcc_only = Baseline { name: "custom".into(), glibc: None, gcc: Some("3.4".into()) };
+    assert_eq!(vec!["libgcc 4.2.0, but custom has 3.4"], req.exceeding(&gcc_only));
+    assert!(Baseline::distro("windows-95").is_err());
+}
+
+#[test]
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+fn symbol_versions_of_test_binary() {
+    let req = SymbolVersionRequirements::of_binary(&std::env::current_exe().unwrap()).unwrap();
+    assert!(req.glibc.unwrap().starts_with("2."));
+}
+
+#[test]
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+fn resolve_native_test() {
+    let db = match ShlibsDatabase::load(Path::new("/"), crate::manifest::get_arch(crate::DEFAULT_TARGET)) {
+        Ok(db) => db,
+        Err(_) => return, // not a Debian-based system
+    };
+    let exe = std::env::current_exe().unwrap();
+    let (deps, missing) = db.resolve(&exe, &HashSet::new()).unwrap();
+    assert!(deps.iter().any(|d| d.starts_with("libc6 (>= 2.")), "{deps:?}");
+    assert!(!deps.iter().any(|d| d.starts_with("libgcc")));
+    assert!(missing.is_empty(), "{missing:?}");
+
+    // as if the package had all of its libraries
+    let needed = elf::dynamic_info(&exe).unwrap().unwrap().needed;
+    let (deps, missing) = db.resolve(&exe, &needed.iter().map(|s| s.as_str()).collect()).unwrap();
+    assert!(deps.is_empty(), "{deps:?}");
+    assert!(missing.is_empty(), "{missing:?}");
+}
+
 #[test]
 #[cfg(target_os = "linux")]
 fn resolve_test() {
     let exe = std::env::current_exe().unwrap();
-    let deps = resolve(&exe, &None).unwrap();
+    let deps = resolve(&exe, &None, None).unwrap();
     assert!(deps.iter().any(|d| d.starts_with("libc")));
     assert!(!deps.iter().any(|d| d.starts_with("libgcc")));
 }
+
+#[test]
+fn sysroot_library_dirs() {
+    let sysroot = tempfile::tempdir().unwrap();
+    let multiarch = sysroot.path().join("usr/lib/aarch64-linux-gnu");
+    fs::create_dir_all(&multiarch).unwrap();
+    fs::create_dir_all(sysroot.path().join("lib")).unwrap();
+    assert_eq!(vec![multiarch, sysroot.path().join("lib"), sysroot.path().join("usr/lib")], library_dirs(Some("aarch64-unknown-linux-gnu"), Some(sysroot.path())));
+}
@@ -225,7 +225,7 @@ pub fn generate(package: &str, assets: &[Asset], options: &Options, listener: &d
     // see: https://salsa.debian.org/debian/debhelper/-/blob/master/dh_installsystemd#L305
     let tmp_file_names = assets
         .iter()
-        .filter(|a| a.c.target_path.starts_with(USR_LIB_TMPFILES_D_DIR))
+        .filter(|a| !a.source.is_dir() && a.c.target_path.starts_with(USR_LIB_TMPFILES_D_DIR))
         .map(|v| fname_from_path(v.source.path().unwrap()))
         .collect::<Vec<String>>()
         .join(" ");
@@ -246,7 +246,7 @@ pub fn generate(package: &str, assets: &[Asset], options: &Options, listener: &d
     installed_non_template_units.extend(
         assets
             .iter()
-            .filter(|a| a.c.target_path.parent() == Some(LIB_SYSTEMD_SYSTEM_DIR.as_ref()))
+            .filter(|a| !a.source.is_dir() && a.c.target_path.parent() == Some(LIB_SYSTEMD_SYSTEM_DIR.as_ref()))
             .map(|a| fname_from_path(a.c.target_path.as_path()))
             .filter(|fname| !fname.contains('@')),
     );
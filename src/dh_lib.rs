@@ -27,7 +27,9 @@ use crate::{listener::Listener, CDResult};
 /// To understand which scripts are invoked when, consult:
 ///   https://www.debian.org/doc/debian-policy/ap-flowcharts.htm
 
-static AUTOSCRIPTS: [(&str, &[u8]); 10] = [
+static AUTOSCRIPTS: [(&str, &[u8]); 12] = [
+    ("postinst-chmod", include_bytes!("../autoscripts/postinst-chmod")),
+    ("postinst-chown", include_bytes!("../autoscripts/postinst-chown")),
     ("postinst-init-tmpfiles", include_bytes!("../autoscripts/postinst-init-tmpfiles")),
     ("postinst-systemd-dont-enable", include_bytes!("../autoscripts/postinst-systemd-dont-enable")),
     ("postinst-systemd-enable", include_bytes!("../autoscripts/postinst-systemd-enable")),
@@ -245,10 +247,10 @@ fn autoscript_sed(snippet_filename: &str, replacements: &HashMap<&str, String>)
 /// # References
 ///
 /// <https://git.launchpad.net/ubuntu/+source/debhelper/tree/lib/Debian/Debhelper/Dh_Lib.pm?h=applied/12.10ubuntu1#n2161>
-fn debhelper_script_subst(user_scripts_dir: &Path, scripts: &mut ScriptFragments, package: &str, script: &str, unit_name: Option<&str>,
+fn debhelper_script_subst(user_scripts_dir: Option<&Path>, scripts: &mut ScriptFragments, package: &str, script: &str, unit_name: Option<&str>,
     listener: &dyn Listener) -> CDResult<()>
 {
-    let user_file = pkgfile(user_scripts_dir, package, package, script, unit_name);
+    let user_file = user_scripts_dir.and_then(|dir| pkgfile(dir, package, package, script, unit_name));
     let mut generated_scripts: Vec<String> = vec![
         format!("{package}.{script}.debhelper"),
         format!("{package}.{script}.service"),
@@ -298,7 +300,7 @@ fn debhelper_script_subst(user_scripts_dir: &Path, scripts: &mut ScriptFragments
 /// on disk supplied by the user.
 ///
 /// See: https://git.launchpad.net/ubuntu/+source/debhelper/tree/dh_installdeb?h=applied/12.10ubuntu1#n300
-pub(crate) fn apply(user_scripts_dir: &Path, scripts: &mut ScriptFragments, package: &str, unit_name: Option<&str>,
+pub(crate) fn apply(user_scripts_dir: Option<&Path>, scripts: &mut ScriptFragments, package: &str, unit_name: Option<&str>,
     listener: &dyn Listener) -> CDResult<()>
 {
     for script in &["postinst", "preinst", "prerm", "postrm"] {
@@ -444,6 +446,8 @@ mod tests {
         actual_scripts.sort();
 
         let expected_scripts = vec![
+            "postinst-chmod",
+            "postinst-chown",
             "postinst-init-tmpfiles",
             "postinst-systemd-dont-enable",
             "postinst-systemd-enable",
@@ -592,7 +596,7 @@ mod tests {
         let mut scripts = ScriptFragments::new();
 
         assert_eq!(0, scripts.len());
-        debhelper_script_subst(Path::new(""), &mut scripts, "mypkg", "myscript", None, &mock_listener).unwrap();
+        debhelper_script_subst(Some(Path::new("")), &mut scripts, "mypkg", "myscript", None, &mock_listener).unwrap();
         assert_eq!(0, scripts.len());
     }
 
@@ -607,7 +611,7 @@ mod tests {
 
         let mut scripts = ScriptFragments::new();
 
-        match debhelper_script_subst(Path::new(""), &mut scripts, "mypkg", "myscript", None, &mock_listener) {
+        match debhelper_script_subst(Some(Path::new("")), &mut scripts, "mypkg", "myscript", None, &mock_listener) {
             Ok(_) => (),
             Err(CargoDebError::DebHelperReplaceFailed(_)) => panic!("Test failed as expected"),
             Err(err) => panic!("Unexpected error {:?}", err),
@@ -626,7 +630,7 @@ mod tests {
         let mut scripts = ScriptFragments::new();
 
         assert_eq!(0, scripts.len());
-        debhelper_script_subst(Path::new(""), &mut scripts, "mypkg", "myscript", None, &mock_listener).unwrap();
+        debhelper_script_subst(Some(Path::new("")), &mut scripts, "mypkg", "myscript", None, &mock_listener).unwrap();
         assert_eq!(1, scripts.len());
         assert!(scripts.contains_key("myscript"));
     }
@@ -645,7 +649,7 @@ mod tests {
         scripts.insert("mypkg.myscript.debhelper".to_owned(), "injected".as_bytes().to_vec());
 
         assert_eq!(1, scripts.len());
-        debhelper_script_subst(Path::new(""), &mut scripts, "mypkg", "myscript", None, &mock_listener).unwrap();
+        debhelper_script_subst(Some(Path::new("")), &mut scripts, "mypkg", "myscript", None, &mock_listener).unwrap();
         assert_eq!(2, scripts.len());
         assert!(scripts.contains_key("mypkg.myscript.debhelper"));
         assert!(scripts.contains_key("myscript"));
@@ -667,7 +671,7 @@ mod tests {
         scripts.insert("mypkg.myscript.debhelper".to_owned(), "injected".as_bytes().to_vec());
 
         assert_eq!(1, scripts.len());
-        debhelper_script_subst(Path::new(""), &mut scripts, "mypkg", "myscript", None, &mock_listener).unwrap();
+        debhelper_script_subst(Some(Path::new("")), &mut scripts, "mypkg", "myscript", None, &mock_listener).unwrap();
         assert_eq!(2, scripts.len());
         assert!(scripts.contains_key("mypkg.myscript.debhelper"));
         assert!(scripts.contains_key("myscript"));
@@ -699,7 +703,7 @@ mod tests {
         scripts.insert(format!("mypkg.{maintainer_script}.service"), "second".as_bytes().to_vec());
 
         assert_eq!(2, scripts.len());
-        debhelper_script_subst(Path::new(""), &mut scripts, "mypkg", maintainer_script, None, &mock_listener).unwrap();
+        debhelper_script_subst(Some(Path::new("")), &mut scripts, "mypkg", maintainer_script, None, &mock_listener).unwrap();
         assert_eq!(3, scripts.len());
         assert!(scripts.contains_key(&format!("mypkg.{maintainer_script}.debhelper")));
         assert!(scripts.contains_key(&format!("mypkg.{maintainer_script}.service")));
@@ -732,7 +736,7 @@ mod tests {
         let mut scripts = ScriptFragments::new();
 
         assert_eq!(0, scripts.len());
-        let result = debhelper_script_subst(Path::new(""), &mut scripts, "mypkg", "myscript", None, &mock_listener);
+        let result = debhelper_script_subst(Some(Path::new("")), &mut scripts, "mypkg", "myscript", None, &mock_listener);
 
         assert!(matches!(result, Err(CargoDebError::Io(_))));
         if let CargoDebError::Io(err) = result.unwrap_err() {
@@ -746,7 +750,7 @@ mod tests {
     fn apply_with_no_matching_files() {
         let mut mock_listener = crate::listener::MockListener::new();
         mock_listener.expect_info().times(0).return_const(());
-        apply(Path::new(""), &mut ScriptFragments::new(), "mypkg", None, &mock_listener).unwrap();
+        apply(Some(Path::new("")), &mut ScriptFragments::new(), "mypkg", None, &mock_listener).unwrap();
     }
 
     #[rstest]
@@ -762,6 +766,6 @@ mod tests {
         let mut mock_listener = crate::listener::MockListener::new();
         mock_listener.expect_info().times(scripts.len()).return_const(());
 
-        apply(Path::new(""), &mut ScriptFragments::new(), "mypkg", None, &mock_listener).unwrap();
+        apply(Some(Path::new("")), &mut ScriptFragments::new(), "mypkg", None, &mock_listener).unwrap();
     }
 }
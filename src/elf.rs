@@ -0,0 +1,835 @@
+//! Minimal ELF parsing and rewriting, enough to find build IDs and strip binaries
+//! without needing binutils for the target.
+//! Works for any architecture, 32 and 64-bit, little and big endian.
+
+use crate::compress::DebugSectionsCompression;
+use crate::error::*;
+use std::fs::File;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+const ELF_MAGIC: &[u8; 4] = b"\x7FELF";
+const ET_EXEC: u16 = 2;
+const ET_DYN: u16 = 3;
+const SHT_NULL: u32 = 0;
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+const SHT_RELA: u32 = 4;
+const SHT_NOTE: u32 = 7;
+const SHT_NOBITS: u32 = 8;
+const SHT_REL: u32 = 9;
+const SHT_DYNAMIC: u32 = 6;
+const SHT_DYNSYM: u32 = 11;
+const SHT_SYMTAB_SHNDX: u32 = 18;
+const SHT_GNU_VERNEED: u32 = 0x6fff_fffe;
+const SHT_GNU_VERSYM: u32 = 0x6fff_ffff;
+const DT_NEEDED: u64 = 1;
+const SHF_ALLOC: u64 = 0x2;
+const SHF_INFO_LINK: u64 = 0x40;
+const SHF_COMPRESSED: u64 = 0x800;
+const SHN_LORESERVE: u16 = 0xff00;
+const PT_LOAD: u32 = 1;
+const PT_PHDR: u32 = 6;
+const PT_NOTE: u32 = 4;
+const NT_GNU_BUILD_ID: u32 = 3;
+const ELFCOMPRESS_ZLIB: u32 = 1;
+const ELFCOMPRESS_ZSTD: u32 = 2;
+
+/// Layout of the file, from `e_ident`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct Class {
+    pub is_64: bool,
+    pub is_le: bool,
+}
+
+impl Class {
+    fn from_ident(ident: &[u8]) -> CDResult<Self> {
+        if ident.len() < 16 || &ident[..4] != ELF_MAGIC {
+            return Err(CargoDebError::Str("not an ELF file"));
+        }
+        let is_64 = match ident[4] {
+            1 => false,
+            2 => true,
+            _ => return Err(CargoDebError::Str("invalid ELF class")),
+        };
+        let is_le = match ident[5] {
+            1 => true,
+            2 => false,
+            _ => return Err(CargoDebError::Str("invalid ELF data encoding")),
+        };
+        Ok(Self { is_64, is_le })
+    }
+
+    pub fn u16(self, b: &[u8], off: usize) -> CDResult<u16> {
+        let b: [u8; 2] = b.get(off..off + 2).ok_or(TRUNCATED)?.try_into().unwrap();
+        Ok(if self.is_le { u16::from_le_bytes(b) } else { u16::from_be_bytes(b) })
+    }
+
+    pub fn u32(self, b: &[u8], off: usize) -> CDResult<u32> {
+        let b: [u8; 4] = b.get(off..off + 4).ok_or(TRUNCATED)?.try_into().unwrap();
+        Ok(if self.is_le { u32::from_le_bytes(b) } else { u32::from_be_bytes(b) })
+    }
+
+    pub fn u64(self, b: &[u8], off: usize) -> CDResult<u64> {
+        let b: [u8; 8] = b.get(off..off + 8).ok_or(TRUNCATED)?.try_into().unwrap();
+        Ok(if self.is_le { u64::from_le_bytes(b) } else { u64::from_be_bytes(b) })
+    }
+
+    /// `Elf32_Addr`/`Elf32_Off`/`Elf32_Word` or their 64-bit equivalents
+    pub fn word(self, b: &[u8], off: usize) -> CDResult<u64> {
+        if self.is_64 { self.u64(b, off) } else { self.u32(b, off).map(u64::from) }
+    }
+
+    pub fn set_u16(self, b: &mut [u8], off: usize, val: u16) {
+        b[off..off + 2].copy_from_slice(&if self.is_le { val.to_le_bytes() } else { val.to_be_bytes() });
+    }
+
+    pub fn set_u32(self, b: &mut [u8], off: usize, val: u32) {
+        b[off..off + 4].copy_from_slice(&if self.is_le { val.to_le_bytes() } else { val.to_be_bytes() });
+    }
+
+    pub fn set_u64(self, b: &mut [u8], off: usize, val: u64) {
+        b[off..off + 8].copy_from_slice(&if self.is_le { val.to_le_bytes() } else { val.to_be_bytes() });
+    }
+
+    /// Values are offsets and sizes taken from a file of the same class, so they fit
+    pub fn set_word(self, b: &mut [u8], off: usize, val: u64) {
+        if self.is_64 { self.set_u64(b, off, val) } else { self.set_u32(b, off, val as u32) }
+    }
+
+    /// Alignment of section headers and other tables of words
+    fn word_size(self) -> u64 {
+        if self.is_64 { 8 } else { 4 }
+    }
+}
+
+const TRUNCATED: CargoDebError = CargoDebError::Str("truncated ELF file");
+
+/// The parts of the ELF header that say where the tables are
+#[derive(Debug, Clone)]
+pub(crate) struct Header {
+    pub class: Class,
+    pub e_type: u16,
+    pub phoff: u64,
+    pub shoff: u64,
+    pub phentsize: u16,
+    pub phnum: u16,
+    pub shentsize: u16,
+    pub shnum: u16,
+    pub shstrndx: u16,
+}
+
+impl Header {
+    pub fn parse(b: &[u8]) -> CDResult<Self> {
+        let class = Class::from_ident(b)?;
+        let (phoff, shoff, rest) = if class.is_64 { (32, 40, 52) } else { (28, 32, 40) };
+        let header = Self {
+            class,
+            e_type: class.u16(b, 16)?,
+            phoff: class.word(b, phoff)?,
+            shoff: class.word(b, shoff)?,
+            phentsize: class.u16(b, rest + 2)?,
+            phnum: class.u16(b, rest + 4)?,
+            shentsize: class.u16(b, rest + 6)?,
+            shnum: class.u16(b, rest + 8)?,
+            shstrndx: class.u16(b, rest + 10)?,
+        };
+        // Sizes of Elf_Shdr and Elf_Phdr. Entries are read at fixed offsets, so they can't be smaller.
+        let (shdr_size, phdr_size) = if class.is_64 { (64, 56) } else { (40, 32) };
+        if header.shentsize < shdr_size {
+            return Err(CargoDebError::Str("invalid section header size in ELF file"));
+        }
+        if header.phentsize < phdr_size {
+            return Err(CargoDebError::Str("invalid program header size in ELF file"));
+        }
+        Ok(header)
+    }
+
+    /// Offset of `e_shoff`, and the following `e_shnum` and `e_shstrndx` fields
+    fn write_section_table(&self, out: &mut [u8], shoff: u64, shnum: u16, shstrndx: u16) {
+        let (shoff_at, rest) = if self.class.is_64 { (40, 52) } else { (32, 40) };
+        self.class.set_word(out, shoff_at, shoff);
+        self.class.set_u16(out, rest + 8, shnum);
+        self.class.set_u16(out, rest + 10, shstrndx);
+    }
+}
+
+/// Entry of the section header table
+#[derive(Debug, Clone)]
+struct Section {
+    name: Vec<u8>,
+    sh_type: u32,
+    flags: u64,
+    addr: u64,
+    offset: u64,
+    size: u64,
+    link: u32,
+    info: u32,
+    addralign: u64,
+    entsize: u64,
+}
+
+impl Section {
+    fn parse(class: Class, b: &[u8]) -> CDResult<Self> {
+        let w = if class.is_64 { 8 } else { 4 };
+        Ok(Self {
+            name: Vec::new(),
+            sh_type: class.u32(b, 4)?,
+            flags: class.word(b, 8)?,
+            addr: class.word(b, 8 + w)?,
+            offset: class.word(b, 8 + 2 * w)?,
+            size: class.word(b, 8 + 3 * w)?,
+            link: class.u32(b, 8 + 4 * w)?,
+            info: class.u32(b, 12 + 4 * w)?,
+            addralign: class.word(b, 16 + 4 * w)?,
+            entsize: class.word(b, 16 + 5 * w)?,
+        })
+    }
+
+    fn write(&self, class: Class, name_offset: u32, out: &mut Vec<u8>) {
+        let w = if class.is_64 { 8 } else { 4 };
+        let start = out.len();
+        out.resize(start + 16 + 6 * w, 0);
+        let b = &mut out[start..];
+        class.set_u32(b, 0, name_offset);
+        class.set_u32(b, 4, self.sh_type);
+        class.set_word(b, 8, self.flags);
+        class.set_word(b, 8 + w, self.addr);
+        class.set_word(b, 8 + 2 * w, self.offset);
+        class.set_word(b, 8 + 3 * w, self.size);
+        class.set_u32(b, 8 + 4 * w, self.link);
+        class.set_u32(b, 12 + 4 * w, self.info);
+        class.set_word(b, 16 + 4 * w, self.addralign);
+        class.set_word(b, 16 + 5 * w, self.entsize);
+    }
+
+    fn is_alloc(&self) -> bool {
+        self.flags & SHF_ALLOC != 0
+    }
+
+    fn is_debug(&self) -> bool {
+        !self.is_alloc() && (self.name.starts_with(b".debug") || self.name.starts_with(b".zdebug"))
+    }
+
+    fn data<'a>(&self, file: &'a [u8]) -> CDResult<&'a [u8]> {
+        if self.sh_type == SHT_NOBITS || self.sh_type == SHT_NULL {
+            return Ok(&[]);
+        }
+        usize::try_from(self.offset).ok()
+            .and_then(|start| file.get(start..start.checked_add(usize::try_from(self.size).ok()?)?))
+            .ok_or(TRUNCATED)
+    }
+}
+
+/// An ELF file loaded into memory, with its section headers
+struct Elf<'a> {
+    data: &'a [u8],
+    header: Header,
+    sections: Vec<Section>,
+}
+
+impl<'a> Elf<'a> {
+    /// `None` if it's not an executable or a shared library, e.g. an object file or a static library
+    fn parse(data: &'a [u8]) -> CDResult<Option<Self>> {
+        if !data.starts_with(ELF_MAGIC) {
+            return Ok(None);
+        }
+        let header = Header::parse(data)?;
+        if header.e_type != ET_EXEC && header.e_type != ET_DYN {
+            return Ok(None);
+        }
+        if header.shnum == 0 || header.shstrndx >= header.shnum {
+            // No sections to strip, or extended section numbering that isn't worth supporting
+            return Err(CargoDebError::Str("unsupported ELF section table"));
+        }
+        let class = header.class;
+        let table_start = usize::try_from(header.shoff).map_err(|_| TRUNCATED)?;
+        let table_end = table_start.checked_add(usize::from(header.shentsize) * usize::from(header.shnum)).ok_or(TRUNCATED)?;
+        let table = data.get(table_start..table_end).ok_or(TRUNCATED)?;
+        let mut sections = table.chunks_exact(header.shentsize.into())
+            .map(|entry| Section::parse(class, entry))
+            .collect::<CDResult<Vec<_>>>()?;
+        let names = sections[usize::from(header.shstrndx)].data(data)?;
+        for (section, entry) in sections.iter_mut().zip(table.chunks_exact(header.shentsize.into())) {
+            let name_start = class.u32(entry, 0)? as usize;
+            let name = names.get(name_start..).ok_or(TRUNCATED)?;
+            section.name = name[..name.iter().position(|&c| c == 0).unwrap_or(name.len())].to_vec();
+        }
+        Ok(Some(Self { data, header, sections }))
+    }
+
+    fn program_headers(&self) -> CDResult<&'a [u8]> {
+        let start = usize::try_from(self.header.phoff).map_err(|_| TRUNCATED)?;
+        let end = start.checked_add(usize::from(self.header.phentsize) * usize::from(self.header.phnum)).ok_or(TRUNCATED)?;
+        self.data.get(start..end).ok_or(TRUNCATED)
+    }
+
+    /// Writes the section name table and the section headers at the end of `out`,
+    /// and points the ELF header to them
+    fn finish(&self, mut out: Vec<u8>, mut sections: Vec<Section>, shstrndx: usize) -> Vec<u8> {
+        let class = self.header.class;
+        let mut names = vec![0u8];
+        let name_offsets: Vec<u32> = sections.iter().map(|s| {
+            if s.name.is_empty() {
+                return 0;
+            }
+            let offset = names.len() as u32;
+            names.extend_from_slice(&s.name);
+            names.push(0);
+            offset
+        }).collect();
+        let shstrtab = &mut sections[shstrndx];
+        shstrtab.offset = out.len() as u64;
+        shstrtab.size = names.len() as u64;
+        shstrtab.sh_type = SHT_STRTAB;
+        shstrtab.flags = 0;
+        shstrtab.addralign = 1;
+        out.extend_from_slice(&names);
+
+        pad_to_alignment(&mut out, class.word_size());
+        let shoff = out.len() as u64;
+        for (section, &name_offset) in sections.iter().zip(&name_offsets) {
+            section.write(class, name_offset, &mut out);
+        }
+        self.header.write_section_table(&mut out, shoff, sections.len() as u16, shstrndx as u16);
+        out
+    }
+}
+
+fn pad_to_alignment(out: &mut Vec<u8>, align: u64) {
+    if align > 1 {
+        let align = align as usize;
+        out.resize((out.len() + align - 1) / align * align, 0);
+    }
+}
+
+/// Appends section's data to the file, and updates its offset
+fn append_section(out: &mut Vec<u8>, section: &mut Section, data: &[u8]) {
+    pad_to_alignment(out, section.addralign);
+    section.offset = out.len() as u64;
+    section.size = data.len() as u64;
+    out.extend_from_slice(data);
+}
+
+/// Reads the header and the table at `offset` without loading the whole file, which may be huge
+fn read_table(file: &mut File, offset: u64, entsize: u16, num: u16) -> CDResult<Vec<u8>> {
+    read_at(file, offset, u64::from(entsize) * u64::from(num))
+}
+
+/// Reads `size` bytes at `offset`, checking that they're in the file before allocating the buffer
+fn read_at(file: &mut File, offset: u64, size: u64) -> CDResult<Vec<u8>> {
+    let end = offset.checked_add(size).ok_or(TRUNCATED)?;
+    if end > file.metadata()?.len() {
+        return Err(TRUNCATED);
+    }
+    let mut data = vec![0; size.try_into().map_err(|_| TRUNCATED)?];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut data)?;
+    Ok(data)
+}
+
+/// GNU build ID (from the `.note.gnu.build-id` section, or `PT_NOTE` segments if sections are missing)
+/// as a lowercase hex string. `None` if the binary has been linked without a build ID.
+pub(crate) fn build_id(path: &Path) -> CDResult<Option<String>> {
+    let mut file = File::open(path)?;
+    let mut header = [0; 64];
+    let len = file.read(&mut header)?;
+    let header = Header::parse(&header[..len])?;
+    let class = header.class;
+
+    // (offset, size) of notes
+    let mut notes = Vec::new();
+    if header.shnum > 0 {
+        let table = read_table(&mut file, header.shoff, header.shentsize, header.shnum)?;
+        for entry in table.chunks_exact(header.shentsize.into()) {
+            if class.u32(entry, 4)? == SHT_NOTE {
+                let (offset, size) = if class.is_64 { (24, 32) } else { (16, 20) };
+                notes.push((class.word(entry, offset)?, class.word(entry, size)?));
+            }
+        }
+    } else if header.phnum > 0 {
+        let table = read_table(&mut file, header.phoff, header.phentsize, header.phnum)?;
+        for entry in table.chunks_exact(header.phentsize.into()) {
+            if class.u32(entry, 0)? == PT_NOTE {
+                let (offset, size) = if class.is_64 { (8, 32) } else { (4, 16) };
+                notes.push((class.word(entry, offset)?, class.word(entry, size)?));
+            }
+        }
+    }
+
+    for (offset, size) in notes {
+        let data = read_at(&mut file, offset, size)?;
+        if let Some(id) = find_note(class, &data, b"GNU\0", NT_GNU_BUILD_ID)? {
+            return Ok(Some(id.iter().map(|b| format!("{b:02x}")).collect()));
+        }
+    }
+    Ok(None)
+}
+
+/// What a dynamically linked executable or library needs from shared libraries
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DynamicInfo {
+    /// Sonames from `DT_NEEDED`
+    pub needed: Vec<String>,
+    /// `(soname, version)` pairs from `.gnu.version_r`, e.g. `("libc.so.6", "GLIBC_2.34")`
+    pub version_needs: Vec<(String, String)>,
+    /// Undefined dynamic symbols
+    pub imports: Vec<ImportedSymbol>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ImportedSymbol {
+    pub name: String,
+    /// Symbol version and the soname it's expected from, if the symbol is versioned
+    pub version: Option<(String, String)>,
+}
+
+/// Reads `DT_NEEDED` entries and versioned symbol references.
+/// `None` if the file is not a dynamically-linked ELF executable or shared library.
+pub(crate) fn dynamic_info(path: &Path) -> CDResult<Option<DynamicInfo>> {
+    let data = fs::read(path)?;
+    let elf = match Elf::parse(&data)? {
+        Some(elf) => elf,
+        None => return Ok(None),
+    };
+    let class = elf.header.class;
+    let section_data = |index: u32| elf.sections.get(index as usize).ok_or(TRUNCATED)?.data(elf.data);
+    let string = |table: &[u8], offset: u64| -> CDResult<String> {
+        let s = table.get(offset as usize..).ok_or(TRUNCATED)?;
+        Ok(String::from_utf8_lossy(&s[..s.iter().position(|&c| c == 0).unwrap_or(s.len())]).into_owned())
+    };
+
+    let dynamic = match elf.sections.iter().find(|s| s.sh_type == SHT_DYNAMIC) {
+        Some(dynamic) => dynamic,
+        None => return Ok(None),
+    };
+    let mut info = DynamicInfo::default();
+    let dynstr = section_data(dynamic.link)?;
+    let w = class.word_size() as usize;
+    for entry in dynamic.data(elf.data)?.chunks_exact(2 * w) {
+        match class.word(entry, 0)? {
+            0 => break,
+            DT_NEEDED => info.needed.push(string(dynstr, class.word(entry, w)?)?),
+            _ => {},
+        }
+    }
+
+    // Version index -> (version name, soname)
+    let mut versions = Vec::new();
+    if let Some(verneed) = elf.sections.iter().find(|s| s.sh_type == SHT_GNU_VERNEED) {
+        let strtab = section_data(verneed.link)?;
+        let data = verneed.data(elf.data)?;
+        let mut offset = 0;
+        for _ in 0..verneed.info {
+            let file = string(strtab, class.u32(data, offset + 4)?.into())?;
+            let mut aux = offset + class.u32(data, offset + 8)? as usize;
+            for _ in 0..class.u16(data, offset + 2)? {
+                let index = class.u16(data, aux + 6)? & 0x7fff;
+                let name = string(strtab, class.u32(data, aux + 8)?.into())?;
+                info.version_needs.push((file.clone(), name.clone()));
+                versions.push((index, name, file.clone()));
+                let next = class.u32(data, aux + 12)? as usize;
+                if next == 0 {
+                    break;
+                }
+                aux += next;
+            }
+            let next = class.u32(data, offset + 12)? as usize;
+            if next == 0 {
+                break;
+            }
+            offset += next;
+        }
+    }
+
+    if let Some(dynsym) = elf.sections.iter().find(|s| s.sh_type == SHT_DYNSYM && s.entsize > 0) {
+        let strtab = section_data(dynsym.link)?;
+        let versym = elf.sections.iter().find(|s| s.sh_type == SHT_GNU_VERSYM).map(|s| s.data(elf.data)).transpose()?;
+        let shndx_at = if class.is_64 { 6 } else { 14 };
+        for (i, sym) in dynsym.data(elf.data)?.chunks_exact(dynsym.entsize as usize).enumerate().skip(1) {
+            if class.u16(sym, shndx_at)? != 0 {
+                continue; // defined here
+            }
+            let name = string(strtab, class.u32(sym, 0)?.into())?;
+            if name.is_empty() {
+                continue;
+            }
+            let index = versym.map(|v| class.u16(v, i * 2)).transpose()?.unwrap_or(0) & 0x7fff;
+            let version = versions.iter().find(|(idx, ..)| *idx == index).map(|(_, name, file)| (name.clone(), file.clone()));
+            info.imports.push(ImportedSymbol { name, version });
+        }
+    }
+    Ok(Some(info))
+}
+
+/// Writes a copy of the ELF file without debug info and symbols that aren't needed at run time
+/// (like `strip --strip-unneeded`). If `debug_path` is given, debug info is first saved there
+/// (like `objcopy --only-keep-debug`), and the stripped file gets a `.gnu_debuglink` to it.
+///
+/// Returns `false` without writing anything if the file isn't an ELF executable or shared library.
+pub(crate) fn strip_file(path: &Path, stripped_path: &Path, debug_path: Option<&Path>, compress_debug: Option<DebugSectionsCompression>) -> CDResult<bool> {
+    let data = fs::read(path)?;
+    let elf = match Elf::parse(&data)? {
+        Some(elf) => elf,
+        None => return Ok(false),
+    };
+    let debuglink = match debug_path {
+        Some(debug_path) => {
+            let debug = only_keep_debug(&elf, compress_debug)?;
+            File::create(debug_path)?.write_all(&debug)?;
+            let mut crc = flate2::Crc::new();
+            crc.update(&debug);
+            let file_name = debug_path.file_name().ok_or(CargoDebError::Str("bad path"))?;
+            Some((file_name.to_str().ok_or(CargoDebError::Str("bad path"))?.to_owned(), crc.sum()))
+        },
+        None => None,
+    };
+    let stripped = strip_unneeded(&elf, debuglink.as_ref().map(|(name, crc)| (name.as_str(), *crc)))?;
+    File::create(stripped_path)?.write_all(&stripped)?;
+    Ok(true)
+}
+
+/// Removes the symbol table and debug sections. Everything that is loaded at run time stays exactly where it was.
+fn strip_unneeded(elf: &Elf<'_>, debuglink: Option<(&str, u32)>) -> CDResult<Vec<u8>> {
+    let class = elf.header.class;
+    let shstrndx = usize::from(elf.header.shstrndx);
+    let sections = &elf.sections;
+
+    let mut removed: Vec<bool> = sections.iter().enumerate().map(|(i, s)| {
+        i != 0 && i != shstrndx && !s.is_alloc() && (s.is_debug() || s.sh_type == SHT_SYMTAB
+            || (s.sh_type == SHT_STRTAB && s.name == b".strtab")
+            || (debuglink.is_some() && s.name == b".gnu_debuglink"))
+    }).collect();
+    // Relocations and extended indices of removed sections go too
+    for (i, s) in sections.iter().enumerate() {
+        let target = match s.sh_type {
+            SHT_REL | SHT_RELA if !s.is_alloc() => s.info,
+            SHT_SYMTAB_SHNDX => s.link,
+            _ => continue,
+        };
+        if removed.get(target as usize).copied().unwrap_or(false) {
+            removed[i] = true;
+        }
+    }
+
+    // The loaded part of the file is kept as-is
+    let mut kept_end = elf.header.phoff.checked_add(u64::from(elf.header.phentsize) * u64::from(elf.header.phnum)).ok_or(TRUNCATED)?;
+    for ph in elf.program_headers()?.chunks_exact(elf.header.phentsize.into()) {
+        let (offset, filesz) = if class.is_64 { (class.u64(ph, 8)?, class.u64(ph, 32)?) } else { (class.u32(ph, 4)?.into(), class.u32(ph, 16)?.into()) };
+        kept_end = kept_end.max(offset.checked_add(filesz).ok_or(TRUNCATED)?);
+    }
+    for s in sections.iter().filter(|s| s.is_alloc() && s.sh_type != SHT_NOBITS) {
+        kept_end = kept_end.max(s.offset.checked_add(s.size).ok_or(TRUNCATED)?);
+    }
+    let kept_end = usize::try_from(kept_end).map_err(|_| TRUNCATED)?.max(if class.is_64 { 64 } else { 52 });
+    let mut out = elf.data.get(..kept_end).ok_or(TRUNCATED)?.to_vec();
+
+    let mut new_index = vec![0u32; sections.len()];
+    let mut new_sections = Vec::with_capacity(sections.len() + 1);
+    for (i, section) in sections.iter().enumerate() {
+        if removed[i] {
+            continue;
+        }
+        new_index[i] = new_sections.len() as u32;
+        let mut section = section.clone();
+        let in_kept_part = section.is_alloc() || section.sh_type == SHT_NULL || section.offset.checked_add(section.size).ok_or(TRUNCATED)? <= kept_end as u64;
+        if i != shstrndx && !in_kept_part {
+            if section.sh_type == SHT_NOBITS {
+                section.offset = out.len() as u64;
+            } else {
+                append_section(&mut out, &mut section, elf.sections[i].data(elf.data)?);
+            }
+        }
+        new_sections.push(section);
+    }
+    for section in &mut new_sections {
+        let remap = |index: u32| new_index.get(index as usize).copied().unwrap_or(index);
+        section.link = remap(section.link);
+        if section.flags & SHF_INFO_LINK != 0 || matches!(section.sh_type, SHT_REL | SHT_RELA) {
+            section.info = remap(section.info);
+        }
+    }
+    // Symbols refer to sections by index, and these may have changed
+    for dynsym in new_sections.iter().filter(|s| s.sh_type == SHT_DYNSYM && s.entsize > 0) {
+        let shndx_at = if class.is_64 { 6 } else { 14 };
+        let start = usize::try_from(dynsym.offset).map_err(|_| TRUNCATED)?;
+        let end = start.checked_add(usize::try_from(dynsym.size).map_err(|_| TRUNCATED)?).ok_or(TRUNCATED)?;
+        for sym in (start..end).step_by(dynsym.entsize as usize) {
+            let shndx = class.u16(&out, sym + shndx_at)?;
+            if shndx != 0 && shndx < SHN_LORESERVE {
+                let new = new_index.get(usize::from(shndx)).copied().unwrap_or(0);
+                class.set_u16(&mut out, sym + shndx_at, new as u16);
+            }
+        }
+    }
+
+    if let Some((file_name, crc)) = debuglink {
+        let mut data = file_name.as_bytes().to_vec();
+        data.push(0);
+        pad_to_alignment(&mut data, 4);
+        let crc_at = data.len();
+        data.extend_from_slice(&[0; 4]);
+        class.set_u32(&mut data, crc_at, crc);
+        let mut section = Section {
+            name: b".gnu_debuglink".to_vec(),
+            sh_type: SHT_PROGBITS,
+            flags: 0, addr: 0, offset: 0, size: 0, link: 0, info: 0,
+            addralign: 4,
+            entsize: 0,
+        };
+        append_section(&mut out, &mut section, &data);
+        new_sections.push(section);
+    }
+
+    let shstrndx = new_index[shstrndx] as usize;
+    Ok(elf.finish(out, new_sections, shstrndx))
+}
+
+/// Keeps only what debuggers need: debug sections, symbols and notes (with the build ID).
+/// Other sections keep their headers, but without their contents.
+fn only_keep_debug(elf: &Elf<'_>, compress: Option<DebugSectionsCompression>) -> CDResult<Vec<u8>> {
+    let class = elf.header.class;
+    let ehsize = if class.is_64 { 64 } else { 52 };
+    let mut out = elf.data.get(..ehsize).ok_or(TRUNCATED)?.to_vec();
+
+    // Program headers describe the original file, so they only stay for reference, without the data
+    pad_to_alignment(&mut out, class.word_size());
+    let phoff = out.len();
+    let phdrs = elf.program_headers()?;
+    out.extend_from_slice(phdrs);
+    let headers_end = (phoff + phdrs.len()) as u64;
+    for ph in out[phoff..].chunks_exact_mut(elf.header.phentsize.into()) {
+        let p_type = class.u32(ph, 0)?;
+        let (offset_at, filesz_at) = if class.is_64 { (8, 32) } else { (4, 16) };
+        let covers_headers = p_type == PT_LOAD && class.word(ph, offset_at)? == 0 && class.word(ph, filesz_at)? >= elf.header.phoff;
+        if p_type == PT_PHDR {
+            class.set_word(ph, offset_at, phoff as u64);
+        } else {
+            // The headers are still there, so keep them mapped like in the original
+            class.set_word(ph, filesz_at, if covers_headers { headers_end } else { 0 });
+            class.set_word(ph, offset_at, 0);
+        }
+    }
+    class.set_word(&mut out, if class.is_64 { 32 } else { 28 }, if phdrs.is_empty() { 0 } else { phoff as u64 });
+
+    let shstrndx = usize::from(elf.header.shstrndx);
+    let mut sections = elf.sections.clone();
+    for (i, section) in sections.iter_mut().enumerate() {
+        if i == 0 || i == shstrndx {
+            continue;
+        }
+        if section.is_alloc() && section.sh_type != SHT_NOTE || section.sh_type == SHT_NOBITS {
+            section.sh_type = SHT_NOBITS;
+            section.offset = out.len() as u64;
+            continue;
+        }
+        let data = elf.sections[i].data(elf.data)?;
+        match compress.filter(|_| section.is_debug() && section.flags & SHF_COMPRESSED == 0 && !data.is_empty()) {
+            Some(method) => {
+                let compressed = compress_section(class, section.addralign, data, method)?;
+                if compressed.len() < data.len() {
+                    section.flags |= SHF_COMPRESSED;
+                    section.addralign = class.word_size();
+                    append_section(&mut out, section, &compressed);
+                } else {
+                    append_section(&mut out, section, data);
+                }
+            },
+            None => append_section(&mut out, section, data),
+        }
+    }
+    Ok(elf.finish(out, sections, shstrndx))
+}
+
+/// `Elf_Chdr` followed by the compressed data, as in `SHF_COMPRESSED` sections
+fn compress_section(class: Class, addralign: u64, data: &[u8], method: DebugSectionsCompression) -> CDResult<Vec<u8>> {
+    let ch_type = match method {
+        DebugSectionsCompression::Zlib => ELFCOMPRESS_ZLIB,
+        DebugSectionsCompression::Zstd => ELFCOMPRESS_ZSTD,
+    };
+    let mut out = vec![0; if class.is_64 { 24 } else { 12 }];
+    class.set_u32(&mut out, 0, ch_type);
+    if class.is_64 {
+        class.set_u64(&mut out, 8, data.len() as u64);
+        class.set_u64(&mut out, 16, addralign);
+    } else {
+        class.set_u32(&mut out, 4, data.len() as u32);
+        class.set_u32(&mut out, 8, addralign as u32);
+    }
+    match method {
+        DebugSectionsCompression::Zlib => {
+            let mut enc = flate2::write::ZlibEncoder::new(out, flate2::Compression::best());
+            enc.write_all(data)?;
+            Ok(enc.finish()?)
+        },
+        #[cfg(feature = "zstd")]
+        DebugSectionsCompression::Zstd => {
+            zstd::stream::copy_encode(data, &mut out, 19)?;
+            Ok(out)
+        },
+        #[cfg(not(feature = "zstd"))]
+        DebugSectionsCompression::Zstd => Err(CargoDebError::Str("cargo-deb has been built without zstd support")),
+    }
+}
+
+/// Descriptor of the first note with the given name and type
+fn find_note<'a>(class: Class, mut data: &'a [u8], name: &[u8], note_type: u32) -> CDResult<Option<&'a [u8]>> {
+    let align4 = |n: usize| (n + 3) & !3;
+    while data.len() >= 12 {
+        let namesz = class.u32(data, 0)? as usize;
+        let descsz = class.u32(data, 4)? as usize;
+        let n_type = class.u32(data, 8)?;
+        let desc_start = 12 + align4(namesz);
+        let desc_end = desc_start + descsz;
+        if desc_end > data.len() {
+            return Err(TRUNCATED);
+        }
+        if n_type == note_type && &data[12..12 + namesz] == name {
+            return Ok(Some(&data[desc_start..desc_end]));
+        }
+        data = &data[align4(desc_end).min(data.len())..];
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_id_of_test_binary() {
+        // Built by cargo, so it's an ELF with a build ID on Linux
+        if !cfg!(target_os = "linux") {
+            return;
+        }
+        let exe = std::env::current_exe().unwrap();
+        let id = build_id(&exe).unwrap();
+        if let Some(id) = id {
+            assert!(id.len() >= 16 && id.bytes().all(|c| c.is_ascii_hexdigit()), "{id}");
+        }
+        assert!(build_id(Path::new("Cargo.toml")).is_err());
+    }
+
+    #[test]
+    fn strip_test_binary() {
+        if !cfg!(target_os = "linux") {
+            return;
+        }
+        let data = fs::read(std::env::current_exe().unwrap()).unwrap();
+        let elf = Elf::parse(&data).unwrap().unwrap();
+        let dynsyms = |elf: &Elf<'_>| elf.sections.iter().find(|s| s.sh_type == SHT_DYNSYM).map(|s| s.data(elf.data).unwrap().to_vec());
+
+        let stripped = strip_unneeded(&elf, Some(("test.debug", 0x1234_5678))).unwrap();
+        let stripped = Elf::parse(&stripped).unwrap().unwrap();
+        assert!(stripped.sections.iter().all(|s| !s.is_debug() && s.sh_type != SHT_SYMTAB));
+        assert_eq!(dynsyms(&elf).map(|d| d.len()), dynsyms(&stripped).map(|d| d.len()));
+        let debuglink = stripped.sections.iter().find(|s| s.name == b".gnu_debuglink").unwrap().data(stripped.data).unwrap();
+        assert_eq!(&debuglink[..12], b"test.debug\0\0");
+        assert_eq!(0x1234_5678, elf.header.class.u32(debuglink, 12).unwrap());
+        // The loaded part is untouched
+        for (a, b) in elf.sections.iter().zip(&stripped.sections).filter(|(a, _)| a.is_alloc() && a.sh_type != SHT_DYNSYM) {
+            assert_eq!(a.name, b.name);
+            assert_eq!(a.data(elf.data).unwrap(), b.data(stripped.data).unwrap());
+        }
+
+        let debug = only_keep_debug(&elf, None).unwrap();
+        let debug = Elf::parse(&debug).unwrap().unwrap();
+        assert_eq!(elf.sections.len(), debug.sections.len());
+        for (a, b) in elf.sections.iter().zip(&debug.sections).filter(|(a, _)| a.is_debug() || a.sh_type == SHT_NOTE) {
+            assert_eq!(a.data(elf.data).unwrap(), b.data(debug.data).unwrap());
+        }
+        assert!(debug.sections.iter().filter(|s| s.is_alloc()).all(|s| s.sh_type == SHT_NOBITS || s.sh_type == SHT_NOTE));
+    }
+
+    #[test]
+    fn dynamic_info_of_test_binary() {
+        if !cfg!(all(target_os = "linux", target_env = "gnu")) {
+            return;
+        }
+        let info = dynamic_info(&std::env::current_exe().unwrap()).unwrap().unwrap();
+        assert!(info.needed.iter().any(|n| n.starts_with("libc.so")), "{info:?}");
+        assert!(info.version_needs.iter().any(|(file, v)| file.starts_with("libc.so") && v.starts_with("GLIBC_2.")));
+        let malloc = info.imports.iter().find(|s| s.name == "malloc").unwrap();
+        assert!(malloc.version.as_ref().unwrap().0.starts_with("GLIBC_"));
+        assert!(dynamic_info(Path::new("Cargo.toml")).unwrap().is_none());
+    }
+
+    #[test]
+    fn invalid_entry_sizes() {
+        if !cfg!(target_os = "linux") {
+            return;
+        }
+        let mut data = fs::read(std::env::current_exe().unwrap()).unwrap();
+        let header = Header::parse(&data).unwrap();
+        let rest = if header.class.is_64 { 52 } else { 40 };
+        header.class.set_u16(&mut data, rest + 6, 0);
+        assert!(Header::parse(&data).is_err());
+        assert!(Elf::parse(&data).is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("zeroed");
+        fs::write(&path, &data).unwrap();
+        assert!(build_id(&path).is_err());
+        assert!(dynamic_info(&path).is_err());
+
+        header.class.set_u16(&mut data, rest + 6, header.shentsize);
+        header.class.set_u16(&mut data, rest + 2, 8);
+        assert!(Header::parse(&data).is_err());
+    }
+
+    #[test]
+    fn overflowing_offsets() {
+        if !cfg!(target_os = "linux") {
+            return;
+        }
+        let mut data = fs::read(std::env::current_exe().unwrap()).unwrap();
+        let elf = Elf::parse(&data).unwrap().unwrap();
+        let class = elf.header.class;
+        let (entsize, table_start) = (usize::from(elf.header.shentsize), elf.header.shoff as usize);
+        let alloc = elf.sections.iter().position(|s| s.is_alloc() && s.sh_type != SHT_NOBITS).unwrap();
+        let note = elf.sections.iter().position(|s| s.sh_type == SHT_NOTE).unwrap();
+        let (offset_at, size_at) = if class.is_64 { (24, 32) } else { (16, 20) };
+        class.set_word(&mut data[table_start + alloc * entsize..], offset_at, u64::MAX - 1);
+        class.set_word(&mut data[table_start + note * entsize..], size_at, u64::MAX / 2);
+        let elf = Elf::parse(&data).unwrap().unwrap();
+        assert!(strip_unneeded(&elf, None).is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("huge-note");
+        fs::write(&path, &data).unwrap();
+        assert!(build_id(&path).is_err());
+    }
+
+    #[test]
+    fn compressed_sections() {
+        let class = Class { is_64: true, is_le: true };
+        let data = b"debug info debug info debug info debug info debug info".repeat(10);
+        let compressed = compress_section(class, 1, &data, DebugSectionsCompression::Zlib).unwrap();
+        assert!(compressed.len() < data.len());
+        assert_eq!(ELFCOMPRESS_ZLIB, class.u32(&compressed, 0).unwrap());
+        assert_eq!(data.len() as u64, class.u64(&compressed, 8).unwrap());
+        let mut decompressed = Vec::new();
+        flate2::read::ZlibDecoder::new(&compressed[24..]).read_to_end(&mut decompressed).unwrap();
+        assert_eq!(data, decompressed);
+
+        let class = Class { is_64: false, is_le: false };
+        let compressed = compress_section(class, 4, &data, DebugSectionsCompression::Zlib).unwrap();
+        assert_eq!(ELFCOMPRESS_ZLIB, class.u32(&compressed, 0).unwrap());
+        assert_eq!(data.len() as u32, class.u32(&compressed, 4).unwrap());
+        assert_eq!(4, class.u32(&compressed, 8).unwrap());
+    }
+
+    #[test]
+    fn notes() {
+        let class = Class { is_64: true, is_le: true };
+        let mut data = Vec::new();
+        data.extend_from_slice(&[4, 0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0]);
+        data.extend_from_slice(b"GNU\0\xAA\xBB\0\0");
+        data.extend_from_slice(&[4, 0, 0, 0, 3, 0, 0, 0, 3, 0, 0, 0]);
+        data.extend_from_slice(b"GNU\0\x01\x02\x03\0");
+        assert_eq!(Some(&[1, 2, 3][..]), find_note(class, &data, b"GNU\0", NT_GNU_BUILD_ID).unwrap());
+        assert_eq!(None, find_note(class, &data, b"Go\0\0", NT_GNU_BUILD_ID).unwrap());
+    }
+}
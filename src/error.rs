@@ -92,6 +92,27 @@ quick_error! {
             display("unable to iterate asset glob result")
             source(err)
         }
+        InvalidCompressionLevel(level: u32, format: crate::compress::Format) {
+            display("compression level {} is not supported by {}", level, format)
+        }
+        DuplicateDirectory(path: PathBuf) {
+            display("directory {} is declared more than once", path.display())
+        }
+        InvalidOwner(owner: String) {
+            display("invalid asset owner '{}', expected \"user:group\" or \"user\"", owner)
+        }
+        InvalidRelation(field: &'static str, relation: String, reason: String) {
+            display("invalid {} relation '{}': {}", field, relation, reason)
+        }
+        InvalidControlField(name: String, reason: String) {
+            display("invalid control field '{}' in extra-fields: {}", name, reason)
+        }
+        UnknownBaseline(name: String, known: String) {
+            display("unknown baseline '{}', expected one of: {}, or a table with glibc and gcc versions", name, known)
+        }
+        DependencyNotFound(soname: String) {
+            display("no dependency information found for {} in the dpkg database", soname)
+        }
         #[cfg(feature = "lzma")]
         LzmaCompressionError(err: xz2::stream::Error) {
             display("lzma compression error: {:?}", err)
@@ -23,32 +23,43 @@ The library interface is experimental. See `main.rs` for usage.
 pub mod compress;
 pub mod control;
 pub mod data;
+pub mod debdiff;
+pub mod debreader;
 pub mod listener;
 pub mod manifest;
+pub mod sbom;
 pub use crate::debarchive::DebArchive;
+pub use crate::debreader::DebPackage;
 pub use crate::error::*;
 pub use crate::manifest::Config;
 
 #[macro_use]
 mod util;
 mod config;
+mod deb822;
 mod debarchive;
+mod dep5;
+mod dep_cache;
 mod dependencies;
 mod dh_installsystemd;
 mod dh_lib;
+mod elf;
 mod error;
 mod ok_or;
 mod pathbytes;
+mod relations;
+mod spdx;
 mod tararchive;
 mod wordsplit;
 
 use crate::listener::Listener;
-use crate::manifest::AssetSource;
+use crate::manifest::{Asset, AssetSource, IsBuilt};
 use rayon::prelude::*;
+use std::borrow::Cow;
 use std::env;
 use std::fs;
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::{Command, ExitStatus};
 
 const TAR_REJECTS_CUR_DIR: bool = true;
@@ -73,10 +84,16 @@ pub fn reset_deb_temp_directory(options: &Config) -> io::Result<()> {
     remove_deb_temp_directory(options);
     // For backwards compatibility with previous cargo-deb behavior, also delete .deb from target/debian,
     // but this time only debs from other versions of the same package
-    let g = deb_dir.join(DebArchive::filename_glob(options));
-    if let Ok(old_files) = glob::glob(g.to_str().expect("utf8 path")) {
-        for old_file in old_files.flatten() {
-            let _ = fs::remove_file(old_file);
+    let mut globs = vec![DebArchive::filename_glob(options)];
+    if options.dbgsym {
+        globs.push(DebArchive::dbgsym_filename_glob(options));
+    }
+    for g in globs {
+        let g = deb_dir.join(g);
+        if let Ok(old_files) = glob::glob(g.to_str().expect("utf8 path")) {
+            for old_file in old_files.flatten() {
+                let _ = fs::remove_file(old_file);
+            }
         }
     }
     fs::create_dir_all(deb_temp_dir)
@@ -101,8 +118,21 @@ pub fn cargo_build(options: &Config, target: Option<&str>, build_command: &str,
     }
     if let Some(target) = target {
         cmd.args(["--target", target]);
-        // Set helpful defaults for cross-compiling
-        if env::var_os("PKG_CONFIG_ALLOW_CROSS").is_none() && env::var_os("PKG_CONFIG_PATH").is_none() {
+    }
+    // Set helpful defaults for cross-compiling
+    let pkg_config_configured = ["PKG_CONFIG_ALLOW_CROSS", "PKG_CONFIG_PATH", "PKG_CONFIG_LIBDIR", "PKG_CONFIG_SYSROOT_DIR"]
+        .iter().any(|var| env::var_os(var).is_some());
+    if !pkg_config_configured {
+        if let Some(sysroot) = &options.sysroot {
+            let multiarch = debian_triple(target.unwrap_or(DEFAULT_TARGET));
+            let pkg_config_dirs = [format!("usr/lib/{multiarch}/pkgconfig"), "usr/lib/pkgconfig".into(), "usr/share/pkgconfig".into()]
+                .iter().map(|dir| sysroot.join(dir))
+                .filter(|dir| dir.exists())
+                .collect::<Vec<_>>();
+            cmd.env("PKG_CONFIG_ALLOW_CROSS", "1");
+            cmd.env("PKG_CONFIG_SYSROOT_DIR", sysroot);
+            cmd.env("PKG_CONFIG_LIBDIR", env::join_paths(pkg_config_dirs).map_err(|_| CargoDebError::Str("invalid sysroot path"))?);
+        } else if let Some(target) = target {
             let pkg_config_path = format!("/usr/lib/{}/pkgconfig", debian_triple(target));
             if Path::new(&pkg_config_path).exists() {
                 cmd.env("PKG_CONFIG_ALLOW_CROSS", "1");
@@ -110,13 +140,7 @@ pub fn cargo_build(options: &Config, target: Option<&str>, build_command: &str,
             }
         }
     }
-    if !options.default_features {
-        cmd.arg("--no-default-features");
-    }
-    let features = &options.features;
-    if !features.is_empty() {
-        cmd.args(["--features", &features.join(",")]);
-    }
+    cmd.args(options.cargo_feature_flags());
 
     log::debug!("cargo build {:?}", cmd.get_args());
 
@@ -158,6 +182,8 @@ fn ensure_success(status: ExitStatus) -> io::Result<()> {
 }
 
 /// Strips the binary that was created with cargo
+///
+/// ELF files are stripped in-process. The `strip` and `objcopy` commands are used only if `options.system_strip` is set.
 pub fn strip_binaries(options: &mut Config, target: Option<&str>, listener: &dyn Listener, separate_file: bool) -> CDResult<()> {
     let mut cargo_config = None;
     let objcopy_tmp;
@@ -165,28 +191,41 @@ pub fn strip_binaries(options: &mut Config, target: Option<&str>, listener: &dyn
     let mut objcopy_cmd = Path::new("objcopy");
     let mut strip_cmd = Path::new("strip");
 
-    if let Some(target) = target {
+    if let (true, Some(target)) = (options.system_strip, target) {
         cargo_config = options.cargo_config()?;
-        if let Some(ref conf) = cargo_config {
-            if let Some(cmd) = conf.objcopy_command(target) {
-                listener.info(format!("Using '{}' for '{target}'", cmd.display()));
-                objcopy_tmp = cmd;
-                objcopy_cmd = &objcopy_tmp;
-            }
+        let sysroot_command = |name| {
+            options.sysroot.as_deref()
+                .and_then(|sysroot| config::sysroot_toolchain_command(sysroot, target, name))
+                .map(Cow::Owned)
+        };
+        if let Some(cmd) = cargo_config.as_ref().and_then(|conf| conf.objcopy_command(target)).or_else(|| sysroot_command("objcopy")) {
+            listener.info(format!("Using '{}' for '{target}'", cmd.display()));
+            objcopy_tmp = cmd;
+            objcopy_cmd = &objcopy_tmp;
+        }
 
-            if let Some(cmd) = conf.strip_command(target) {
-                listener.info(format!("Using '{}' for '{target}'", cmd.display()));
-                strip_tmp = cmd;
-                strip_cmd = &strip_tmp;
-            }
+        if let Some(cmd) = cargo_config.as_ref().and_then(|conf| conf.strip_command(target)).or_else(|| sysroot_command("strip")) {
+            listener.info(format!("Using '{}' for '{target}'", cmd.display()));
+            strip_tmp = cmd;
+            strip_cmd = &strip_tmp;
         }
     }
+    let system_strip = options.system_strip;
+    let system_tools = SystemStripTools {
+        strip_cmd,
+        objcopy_cmd,
+        target,
+        conf_path: cargo_config.as_ref().map(|c| c.path()).unwrap_or_else(|| Path::new(".cargo/config")),
+    };
 
     let stripped_binaries_output_dir = options.default_deb_output_dir();
+    let debug_path_symlinks = options.debug_path_symlinks;
+    let compress_debug_symbols = options.compress_debug_symbols;
 
-    options.built_binaries_mut().into_par_iter().enumerate()
+    let stripped = options.built_binaries_mut().into_par_iter().enumerate()
         .filter(|(_, asset)| !asset.source.archive_as_symlink_only()) // data won't be included, so nothing to strip
-        .try_for_each(|(i, asset)| {
+        .map(|(i, asset)| {
+        let mut debug_asset = None;
         let new_source = match asset.source.path() {
             Some(path) => {
                 if !path.exists() {
@@ -195,65 +234,31 @@ pub fn strip_binaries(options: &mut Config, target: Option<&str>, listener: &dyn
 
                 // The debug_path and debug_filename should never return None if we have an AssetSource::Path
                 let debug_path = asset.source.debug_source().expect("Failed to compute debug source path");
-                let conf_path = cargo_config.as_ref().map(|c| c.path())
-                    .unwrap_or_else(|| Path::new(".cargo/config"));
 
-                if separate_file {
-                    log::debug!("extracting debug info of {} with {}", path.display(), objcopy_cmd.display());
-                    let _ = std::fs::remove_file(&debug_path);
-                    Command::new(objcopy_cmd)
-                        .arg("--only-keep-debug")
-                        .arg(path)
-                        .arg(&debug_path)
-                        .status()
-                        .and_then(ensure_success)
-                        .map_err(|err| {
-                            if let Some(target) = target {
-                                CargoDebError::StripFailed(path.to_owned(), format!("{}: {}.\nhint: Target-specific strip commands are configured in [target.{}] objcopy = {{ path =\"{}\" }} in {}", objcopy_cmd.display(), err, target, objcopy_cmd.display(), conf_path.display()))
-                            } else {
-                                CargoDebError::CommandFailed(err, "objcopy")
-                            }
-                        })?;
-                }
+                // Read before stripping, in case the strip command drops the note
+                let build_id = if separate_file { elf::build_id(path).ok().flatten() } else { None };
 
                 let file_name = path.file_name().ok_or(CargoDebError::Str("bad path"))?;
                 let file_name = format!("{}.tmp{}-stripped", file_name.to_string_lossy(), i);
                 let stripped_temp_path = stripped_binaries_output_dir.join(file_name);
                 let _ = std::fs::remove_file(&stripped_temp_path);
+                if separate_file {
+                    let _ = std::fs::remove_file(&debug_path);
+                }
 
-                log::debug!("stripping {} with {}", path.display(), strip_cmd.display());
-                Command::new(strip_cmd)
-                   .arg("--strip-unneeded")
-                   .arg("-o")
-                   .arg(&stripped_temp_path)
-                   .arg(path)
-                   .status()
-                   .and_then(ensure_success)
-                   .map_err(|err| {
-                        if let Some(target) = target {
-                            CargoDebError::StripFailed(path.to_owned(), format!("{}: {}.\nhint: Target-specific strip commands are configured in [target.{}] strip = {{ path = \"{}\" }} in {}", strip_cmd.display(), err, target, strip_cmd.display(), conf_path.display()))
-                        } else {
-                            CargoDebError::CommandFailed(err, "strip")
-                        }
-                    })?;
-
-                if !stripped_temp_path.exists() {
-                    return Err(CargoDebError::StripFailed(path.to_owned(), format!("{} command failed to create output '{}'", strip_cmd.display(), stripped_temp_path.display())));
+                let stripped_natively = !system_strip && {
+                    log::debug!("stripping {}", path.display());
+                    elf::strip_file(path, &stripped_temp_path, separate_file.then(|| debug_path.as_path()), compress_debug_symbols)
+                        .map_err(|err| CargoDebError::StripFailed(path.to_owned(), format!("{err}\nhint: use --system-strip to strip with the strip and objcopy commands instead")))?
+                };
+                if !stripped_natively {
+                    // Formats other than ELF executables and shared libraries are left to the strip command
+                    system_tools.strip(path, &stripped_temp_path, separate_file.then(|| debug_path.as_path()), compress_debug_symbols)?;
                 }
 
                 if separate_file {
-                    log::debug!("linking debug info to {} with {}", debug_path.display(), objcopy_cmd.display());
-                    let debug_filename = debug_path.file_name().expect("Built binary has no filename");
-                    Command::new(objcopy_cmd)
-                        .current_dir(debug_path.parent().expect("Debug source file had no parent path"))
-                        .arg(format!(
-                            "--add-gnu-debuglink={}",
-                            debug_filename.to_str().expect("Debug source file had no filename")
-                        ))
-                        .arg(&stripped_temp_path)
-                        .status()
-                        .and_then(ensure_success)
-                        .map_err(|err| CargoDebError::CommandFailed(err, "objcopy"))?;
+                    let debug_target = asset.c.debug_target().expect("Built binary has no debug target");
+                    debug_asset = Some((debug_assets(debug_path, debug_target, build_id.as_deref(), debug_path_symlinks), build_id));
                 }
                 listener.info(format!("Stripped '{}'", path.display()));
                 AssetSource::Path(stripped_temp_path)
@@ -261,17 +266,107 @@ pub fn strip_binaries(options: &mut Config, target: Option<&str>, listener: &dyn
             None => {
                 // This is unexpected - emit a warning if we come across it
                 listener.warning(format!("Found built asset with non-path source '{:?}'", asset));
-                return Ok(());
+                return Ok(None);
             },
         };
         asset.source = new_source;
-        Ok::<_, CargoDebError>(())
-    })?;
+        Ok::<_, CargoDebError>(debug_asset)
+    }).collect::<CDResult<Vec<_>>>()?;
 
     if separate_file {
         // If we want to debug symbols included in a separate file, add these files to the debian assets
-        options.add_debug_assets();
+        let (debug_assets, build_ids): (Vec<_>, Vec<_>) = stripped.into_iter().flatten().unzip();
+        options.build_ids = build_ids.into_iter().flatten().collect();
+        options.build_ids.sort_unstable();
+        options.build_ids.dedup();
+        options.add_debug_assets(debug_assets.into_iter().flatten().collect());
     }
 
     Ok(())
 }
+
+/// Binutils commands for `--system-strip`
+struct SystemStripTools<'a> {
+    strip_cmd: &'a Path,
+    objcopy_cmd: &'a Path,
+    target: Option<&'a str>,
+    conf_path: &'a Path,
+}
+
+impl SystemStripTools<'_> {
+    fn strip(&self, path: &Path, stripped_temp_path: &Path, debug_path: Option<&Path>, compress_debug_symbols: Option<compress::DebugSectionsCompression>) -> CDResult<()> {
+        let Self { strip_cmd, objcopy_cmd, target, conf_path } = *self;
+
+        if let Some(debug_path) = debug_path {
+            log::debug!("extracting debug info of {} with {}", path.display(), objcopy_cmd.display());
+            let mut cmd = Command::new(objcopy_cmd);
+            cmd.arg("--only-keep-debug");
+            if let Some(method) = compress_debug_symbols {
+                cmd.arg(format!("--compress-debug-sections={method}"));
+            }
+            cmd.arg(path)
+                .arg(debug_path)
+                .status()
+                .and_then(ensure_success)
+                .map_err(|err| {
+                    if let Some(target) = target {
+                        CargoDebError::StripFailed(path.to_owned(), format!("{}: {}.\nhint: Target-specific strip commands are configured in [target.{}] objcopy = {{ path =\"{}\" }} in {}", objcopy_cmd.display(), err, target, objcopy_cmd.display(), conf_path.display()))
+                    } else {
+                        CargoDebError::CommandFailed(err, "objcopy")
+                    }
+                })?;
+        }
+
+        log::debug!("stripping {} with {}", path.display(), strip_cmd.display());
+        Command::new(strip_cmd)
+           .arg("--strip-unneeded")
+           .arg("-o")
+           .arg(stripped_temp_path)
+           .arg(path)
+           .status()
+           .and_then(ensure_success)
+           .map_err(|err| {
+                if let Some(target) = target {
+                    CargoDebError::StripFailed(path.to_owned(), format!("{}: {}.\nhint: Target-specific strip commands are configured in [target.{}] strip = {{ path = \"{}\" }} in {}", strip_cmd.display(), err, target, strip_cmd.display(), conf_path.display()))
+                } else {
+                    CargoDebError::CommandFailed(err, "strip")
+                }
+            })?;
+
+        if !stripped_temp_path.exists() {
+            return Err(CargoDebError::StripFailed(path.to_owned(), format!("{} command failed to create output '{}'", strip_cmd.display(), stripped_temp_path.display())));
+        }
+
+        if let Some(debug_path) = debug_path {
+            log::debug!("linking debug info to {} with {}", debug_path.display(), objcopy_cmd.display());
+            let debug_filename = debug_path.file_name().expect("Built binary has no filename");
+            Command::new(objcopy_cmd)
+                .current_dir(debug_path.parent().expect("Debug source file had no parent path"))
+                .arg(format!(
+                    "--add-gnu-debuglink={}",
+                    debug_filename.to_str().expect("Debug source file had no filename")
+                ))
+                .arg(stripped_temp_path)
+                .status()
+                .and_then(ensure_success)
+                .map_err(|err| CargoDebError::CommandFailed(err, "objcopy"))?;
+        }
+        Ok(())
+    }
+}
+
+/// Debug info goes to `/usr/lib/debug/.build-id/`, where debuggers look for it.
+/// Binaries without a build ID use the path-based `/usr/lib/debug/<path>.debug` location instead.
+fn debug_assets(debug_source: PathBuf, path_target: PathBuf, build_id: Option<&str>, keep_path_symlink: bool) -> Vec<Asset> {
+    let build_id_target = match build_id.and_then(manifest::build_id_debug_target) {
+        Some(target) => target,
+        None => return vec![Asset::new(AssetSource::Path(debug_source), path_target, 0o644, IsBuilt::No)],
+    };
+    let mut assets = Vec::with_capacity(2);
+    if keep_path_symlink {
+        let link = manifest::relative_symlink_target(&path_target, &build_id_target);
+        assets.push(Asset::new(AssetSource::SymlinkTo(link), path_target, 0o777, IsBuilt::No));
+    }
+    assets.push(Asset::new(AssetSource::Path(debug_source), build_id_target, 0o644, IsBuilt::No));
+    assets
+}
@@ -1,6 +1,7 @@
 use cargo_deb::*;
 use cargo_deb::control::ControlArchiveBuilder;
 use std::env;
+use std::fs;
 use std::path::Path;
 use std::process;
 use std::time;
@@ -9,6 +10,10 @@ struct CliOptions {
     no_build: bool,
     strip_override: Option<bool>,
     separate_debug_symbols: bool,
+    dbgsym: bool,
+    compress_debug_symbols: Option<String>,
+    system_strip: bool,
+    no_dep_cache: bool,
     fast: bool,
     verbose: bool,
     quiet: bool,
@@ -17,13 +22,19 @@ struct CliOptions {
     output_path: Option<String>,
     variant: Option<String>,
     target: Option<String>,
+    sysroot: Option<String>,
     manifest_path: Option<String>,
     cargo_build_cmd: String,
     cargo_build_flags: Vec<String>,
     deb_version: Option<String>,
     deb_revision: Option<String>,
     system_xz: bool,
+    compress_type: Option<String>,
+    compress_level: Option<String>,
+    compress_threads: Option<String>,
+    compress_system: bool,
     profile: Option<String>,
+    timestamp: Option<String>,
 }
 
 fn main() {
@@ -36,9 +47,14 @@ fn main() {
     cli_opts.optflag("", "no-strip", "Do not strip debug symbols from the binary");
     cli_opts.optflag("", "strip", "Always try to strip debug symbols");
     cli_opts.optflag("", "separate-debug-symbols", "Strip debug symbols into a separate .debug file");
+    cli_opts.optflagopt("", "compress-debug-symbols", "Compress DWARF sections of separate debug symbols", "zlib|zstd");
+    cli_opts.optflag("", "dbgsym", "Put debug symbols in a separate -dbgsym.ddeb package");
+    cli_opts.optflag("", "system-strip", "Strip using command-line strip and objcopy commands instead of built-in");
+    cli_opts.optflag("", "no-dep-cache", "Don't reuse dependencies of unchanged binaries from previous runs");
     cli_opts.optflag("", "fast", "Use faster compression, which yields larger archive");
     cli_opts.optflag("", "install", "Immediately install created package");
     cli_opts.optopt("", "target", "Rust target for cross-compilation", "triple");
+    cli_opts.optopt("", "sysroot", "Root of the target system's files, for finding its libraries", "path");
     cli_opts.optopt("", "variant", "Alternative configuration section to use", "name");
     cli_opts.optopt("", "manifest-path", "Cargo project file location", "./Cargo.toml");
     cli_opts.optopt("p", "package", "Select one of packages belonging to a workspace", "name");
@@ -50,8 +66,14 @@ fn main() {
     cli_opts.optopt("", "deb-version", "Alternate version string for package", "version");
     cli_opts.optopt("", "deb-revision", "Alternate revision string for package", "revision");
     cli_opts.optflag("", "system-xz", "Compress using command-line xz command instead of built-in");
+    cli_opts.optopt("", "compress-type", "Compress the archives with gz, xz, zst or none", "type");
+    cli_opts.optopt("", "compress-level", "Compression level, e.g. 9 or 9e for xz", "level");
+    cli_opts.optopt("", "compress-threads", "Number of threads used by xz and zst compression", "num");
+    cli_opts.optflag("", "compress-system", "Compress using command-line xz/gzip/zstd command instead of built-in");
     cli_opts.optopt("", "profile", "select which project profile to package", "profile");
     cli_opts.optopt("", "cargo-build", "Override cargo build subcommand", "subcommand");
+    cli_opts.optopt("", "timestamp", "Unix time of files in the package (default: $SOURCE_DATE_EPOCH or now)", "seconds");
+    cli_opts.optflag("", "json", "Print JSON instead of text (for inspect and diff)");
 
     let matches = match cli_opts.parse(&args[1..]) {
         Ok(m) => m,
@@ -60,7 +82,7 @@ fn main() {
         }
     };
     if matches.opt_present("h") {
-        print!("{}", cli_opts.usage("Usage: cargo deb [options] [-- <cargo build flags>]"));
+        print!("{}", cli_opts.usage("Usage: cargo deb [options] [-- <cargo build flags>]\n       cargo deb inspect [--json] <file.deb>\n       cargo deb diff [--json] <old.deb> <new.deb>"));
         return;
     }
 
@@ -69,11 +91,31 @@ fn main() {
         return;
     }
 
+    // `cargo deb` invocation passes the `deb` arg through.
+    let subcommand_args = matches.free.strip_prefix(&["deb".to_string()]).unwrap_or(&matches.free);
+    let subcommand = match subcommand_args.split_first().map(|(cmd, args)| (cmd.as_str(), args)) {
+        Some(("inspect", args)) => Some(inspect(args, matches.opt_present("json"))),
+        Some(("diff", args)) => Some(diff(args, matches.opt_present("json"))),
+        _ => None,
+    };
+    if let Some(res) = subcommand {
+        if let Err(err) = res {
+            err_exit(&err);
+        }
+        return;
+    }
+
     let install = matches.opt_present("install");
     match process(CliOptions {
         no_build: matches.opt_present("no-build"),
         strip_override: if matches.opt_present("strip") { Some(true) } else if matches.opt_present("no-strip") { Some(false) } else { None },
         separate_debug_symbols: matches.opt_present("separate-debug-symbols"),
+        dbgsym: matches.opt_present("dbgsym"),
+        compress_debug_symbols: if matches.opt_present("compress-debug-symbols") {
+            Some(matches.opt_str("compress-debug-symbols").unwrap_or_else(|| "zlib".into()))
+        } else { None },
+        system_strip: matches.opt_present("system-strip"),
+        no_dep_cache: matches.opt_present("no-dep-cache"),
         quiet: matches.opt_present("quiet"),
         verbose: matches.opt_present("verbose"),
         install,
@@ -81,13 +123,19 @@ fn main() {
         fast: install || matches.opt_present("fast"),
         variant: matches.opt_str("variant"),
         target: matches.opt_str("target"),
+        sysroot: matches.opt_str("sysroot"),
         output_path: matches.opt_str("output"),
         package_name: matches.opt_str("package"),
         manifest_path: matches.opt_str("manifest-path"),
         deb_version: matches.opt_str("deb-version"),
         deb_revision: matches.opt_str("deb-revision"),
         system_xz: matches.opt_present("system-xz"),
+        compress_type: matches.opt_str("compress-type"),
+        compress_level: matches.opt_str("compress-level"),
+        compress_threads: matches.opt_str("compress-threads"),
+        compress_system: matches.opt_present("compress-system"),
         profile: matches.opt_str("profile"),
+        timestamp: matches.opt_str("timestamp"),
         cargo_build_cmd: matches.opt_str("cargo-build").unwrap_or("build".to_string()),
         cargo_build_flags: matches.free,
     }) {
@@ -121,10 +169,15 @@ fn process(
         package_name,
         variant,
         target,
+        sysroot,
         install,
         no_build,
         strip_override,
         separate_debug_symbols,
+        dbgsym,
+        compress_debug_symbols,
+        system_strip,
+        no_dep_cache,
         quiet,
         fast,
         verbose,
@@ -133,7 +186,12 @@ fn process(
         deb_version,
         deb_revision,
         system_xz,
+        compress_type,
+        compress_level,
+        compress_threads,
+        compress_system,
         profile,
+        timestamp,
     }: CliOptions,
 ) -> CDResult<()> {
     let target = target.as_deref();
@@ -171,6 +229,12 @@ fn process(
     }
     cargo_build_flags.push(format!("--profile={selected_profile}"));
 
+    if let Some(timestamp) = timestamp {
+        let timestamp: u64 = timestamp.parse().map_err(|e| CargoDebError::NumParse("invalid --timestamp", e))?;
+        // Overrides SOURCE_DATE_EPOCH before anything reads it, including assets generated by the config and the build
+        env::set_var("SOURCE_DATE_EPOCH", timestamp.to_string());
+    }
+
     let manifest_path = manifest_path.as_ref().map_or("Cargo.toml", |s| s.as_str());
     let mut options = Config::from_manifest(
         Path::new(manifest_path),
@@ -183,8 +247,41 @@ fn process(
         listener,
         selected_profile,
     )?;
+    if dbgsym {
+        options.dbgsym = true;
+    }
+    if options.dbgsym {
+        options.separate_debug_symbols = true;
+    }
+    let separate_debug_symbols = separate_debug_symbols || options.separate_debug_symbols;
+    if let Some(method) = compress_debug_symbols {
+        options.compress_debug_symbols = Some(method.parse()?);
+    }
     reset_deb_temp_directory(&options)?;
 
+    if let Some(compress_type) = compress_type {
+        options.compression.format = compress_type.parse()?;
+    } else if system_xz {
+        options.compression.format = compress::Format::Xz;
+    }
+    if let Some(level) = compress_level {
+        options.compression.level = Some(level.parse()?);
+    }
+    if let Some(threads) = compress_threads {
+        options.compression.threads = Some(threads.parse().map_err(|e| CargoDebError::NumParse("invalid number of compression threads", e))?);
+    }
+    if fast {
+        options.compression.fast = true;
+    }
+    if compress_system || system_xz {
+        options.compression.use_system = true;
+    }
+    options.system_strip = system_strip;
+    options.dep_cache = !no_dep_cache;
+    if let Some(sysroot) = sysroot {
+        options.sysroot = Some(sysroot.into());
+    }
+
     options.extend_cargo_build_flags(&mut cargo_build_flags);
 
     if !no_build {
@@ -201,52 +298,110 @@ fn process(
         log::debug!("not stripping profile.release.debug={} strip-flag={:?}", options.debug_enabled, strip_override);
     }
 
-    // Obtain the current time which will be used to stamp the generated files in the archives.
-    let system_time = time::SystemTime::now().duration_since(time::UNIX_EPOCH)?.as_secs();
+    // Obtain the time which will be used to stamp the generated files in the archives.
+    // For reproducible builds it's fixed, otherwise it's the current time.
+    let system_time = match options.source_date_epoch {
+        Some(timestamp) => timestamp,
+        None => time::SystemTime::now().duration_since(time::UNIX_EPOCH)?.as_secs(),
+    };
+
+    let dbgsym_package = if options.dbgsym { options.split_dbgsym_package() } else { None };
+    if let Some(dbgsym_package) = &dbgsym_package {
+        let generated = write_deb(dbgsym_package, system_time, listener)?;
+        if !quiet {
+            println!("{}", generated.display());
+        }
+    }
+
+    let generated = write_deb(&options, system_time, listener)?;
+    if !quiet {
+        println!("{}", generated.display());
+    }
+
+    remove_deb_temp_directory(&options);
+
+    if install {
+        install_deb(&generated)?;
+    }
+    Ok(())
+}
 
-    let options = &options;
+/// Builds the control and data archives, and puts them together in a `.deb` file
+fn write_deb(options: &Config, system_time: u64, listener: &dyn listener::Listener) -> CDResult<std::path::PathBuf> {
+    // Compressed archives are kept in temporary files, so that large packages don't need to fit in memory
+    let temp_dir = options.deb_temp_dir();
+    let temp_dir = temp_dir.as_path();
     let (control_builder, data_result) = rayon::join(
         move || {
             // The control archive is the metadata for the package manager
-            let mut control_builder = ControlArchiveBuilder::new(compress::xz_or_gz(fast, system_xz)?, system_time, listener);
+            let mut control_builder = ControlArchiveBuilder::new(compress::select_compressor(&options.compression, temp_dir)?, system_time, listener);
             control_builder.generate_archive(options)?;
             Ok::<_, CargoDebError>(control_builder)
         },
         move || {
             // Initialize the contents of the data archive (files that go into the filesystem).
-            let (compressed, asset_hashes) = data::generate_archive(compress::xz_or_gz(fast, system_xz)?, &options, system_time, listener)?;
+            let (compressed, asset_hashes) = data::generate_archive(compress::select_compressor(&options.compression, temp_dir)?, options, system_time, listener)?;
             let original_data_size = compressed.uncompressed_size;
             Ok::<_, CargoDebError>((compressed.finish()?, original_data_size, asset_hashes))
         },
     );
     let mut control_builder = control_builder?;
     let (data_compressed, original_data_size, asset_hashes) = data_result?;
-    control_builder.generate_md5sums(options, asset_hashes)?;
+    let sboms = options.sbom.iter()
+        .map(|&format| Ok((format, sbom::generate(format, options, &asset_hashes, system_time)?)))
+        .collect::<CDResult<Vec<_>>>()?;
+    control_builder.generate_md5sums(options, asset_hashes.into_iter().map(|(path, hashes)| (path, hashes.md5)).collect())?;
     let control_compressed = control_builder.finish()?.finish()?;
 
-    let mut deb_contents = DebArchive::new(&options)?;
+    let mut deb_contents = DebArchive::new(options)?;
     deb_contents.add_data("debian-binary".into(), system_time, b"2.0\n")?;
 
     // Order is important for Debian
-    deb_contents.add_data(format!("control.tar.{}", control_compressed.extension()), system_time, &control_compressed)?;
-    drop(control_compressed);
-    let compressed_data_size = data_compressed.len();
+    deb_contents.add_file(control_compressed.format().tar_file_name("control"), system_time, control_compressed.size(), control_compressed)?;
+    let compressed_data_size = data_compressed.size();
     listener.info(format!(
         "compressed/original ratio {compressed_data_size}/{original_data_size} ({}%)",
-        compressed_data_size * 100 / original_data_size
+        compressed_data_size * 100 / original_data_size.max(1)
     ));
-    deb_contents.add_data(format!("data.tar.{}", data_compressed.extension()), system_time, &data_compressed)?;
-    drop(data_compressed);
+    deb_contents.add_file(data_compressed.format().tar_file_name("data"), system_time, compressed_data_size, data_compressed)?;
 
     let generated = deb_contents.finish()?;
-    if !quiet {
-        println!("{}", generated.display());
+    for (format, sbom) in sboms {
+        let sbom_path = generated.with_extension(format.extension());
+        fs::write(&sbom_path, sbom).map_err(|e| CargoDebError::IoFile("unable to write SBOM", e, sbom_path.clone()))?;
+        listener.info(format!("SBOM written to {}", sbom_path.display()));
     }
+    Ok(generated)
+}
 
-    remove_deb_temp_directory(options);
+/// Prints contents of an existing `.deb` file, without needing `dpkg-deb`
+fn inspect(args: &[String], json: bool) -> CDResult<()> {
+    let deb_path = match args {
+        [deb_path] => deb_path,
+        _ => return Err("specify path to one .deb file to inspect".into()),
+    };
+    let deb = DebPackage::read_file(Path::new(deb_path))?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&deb)?);
+    } else {
+        print!("{deb}");
+    }
+    Ok(())
+}
 
-    if install {
-        install_deb(&generated)?;
+/// Prints what has changed between two `.deb` files
+fn diff(args: &[String], json: bool) -> CDResult<()> {
+    let (old_path, new_path) = match args {
+        [old_path, new_path] => (old_path, new_path),
+        _ => return Err("specify paths to the old and the new .deb file to compare".into()),
+    };
+    let old = DebPackage::read_file(Path::new(old_path))?;
+    let new = DebPackage::read_file(Path::new(new_path))?;
+    let diff = debdiff::diff(&old, &new);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&diff)?);
+    } else {
+        print!("{diff}");
     }
     Ok(())
 }
@@ -1,22 +1,29 @@
+use crate::compress::{CompressConfig, DebugSectionsCompression, Format, Level};
 use crate::config::CargoConfig;
-use crate::dependencies::resolve;
+use crate::deb822;
+use crate::dep_cache::DependencyCache;
+use crate::dependencies::{merge_dependencies, resolve, Baseline, ShlibsDatabase, SymbolVersionRequirements};
 use crate::dh_installsystemd;
 use crate::error::{CDResult, CargoDebError};
 use crate::listener::Listener;
 use crate::ok_or::OkOrThen;
 use crate::pathbytes::AsUnixPathBytes;
+use crate::relations::{RelationField, RelationList};
+use crate::spdx::LicenseExpr;
 use crate::util::read_file_to_bytes;
 use cargo_toml::DebugSetting;
 use cargo_toml::OptionalFile;
 use rayon::prelude::*;
 use serde::Deserialize;
 use std::borrow::Cow;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env::consts::EXE_SUFFIX;
 use std::env::consts::{DLL_PREFIX, DLL_SUFFIX};
+use std::fmt;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::str::FromStr;
 
 fn is_glob_pattern(s: &Path) -> bool {
     s.to_bytes().iter().any(|&c| c == b'*' || c == b'[' || c == b']' || c == b'!')
@@ -28,8 +35,12 @@ pub enum AssetSource {
     Path(PathBuf),
     /// A symlink existing in the file system
     Symlink(PathBuf),
+    /// Create a symlink pointing to the given (usually relative) path
+    SymlinkTo(PathBuf),
     /// Write data to destination as-is.
     Data(Vec<u8>),
+    /// Create a directory, even if it's empty
+    Dir,
 }
 
 impl AssetSource {
@@ -57,7 +68,12 @@ impl AssetSource {
     }
 
     pub fn archive_as_symlink_only(&self) -> bool {
-        matches!(self, AssetSource::Symlink(_))
+        matches!(self, AssetSource::Symlink(_) | AssetSource::SymlinkTo(_))
+    }
+
+    #[must_use]
+    pub fn is_dir(&self) -> bool {
+        matches!(self, AssetSource::Dir)
     }
 
     #[must_use]
@@ -66,7 +82,7 @@ impl AssetSource {
             // FIXME: may not be accurate if the executable is not stripped yet?
             AssetSource::Path(ref p) => fs::metadata(p).ok().map(|m| m.len()),
             AssetSource::Data(ref d) => Some(d.len() as u64),
-            AssetSource::Symlink(_) => None,
+            AssetSource::Symlink(_) | AssetSource::SymlinkTo(_) | AssetSource::Dir => None,
         }
     }
 
@@ -78,7 +94,8 @@ impl AssetSource {
                 Cow::Owned(data)
             },
             AssetSource::Data(d) => Cow::Borrowed(d),
-            AssetSource::Symlink(_) => return Err(CargoDebError::Str("Symlink unexpectedly used to read file data")),
+            AssetSource::Symlink(_) | AssetSource::SymlinkTo(_) => return Err(CargoDebError::Str("Symlink unexpectedly used to read file data")),
+            AssetSource::Dir => return Err(CargoDebError::Str("Directory unexpectedly used to read file data")),
         })
     }
 
@@ -144,7 +161,7 @@ impl Assets {
         }
     }
 
-    fn with_resolved_assets(assets: Vec<Asset>) -> Assets {
+    pub(crate) fn with_resolved_assets(assets: Vec<Asset>) -> Assets {
         Assets {
             unresolved: vec![],
             resolved: assets,
@@ -181,9 +198,59 @@ pub struct UnresolvedAsset {
 pub struct AssetCommon {
     pub target_path: PathBuf,
     pub chmod: u32,
+    /// `None` means root:root
+    pub owner: Option<Owner>,
     is_built: IsBuilt,
 }
 
+/// User and group of an installed file, in `chown` syntax (`user:group`, `user`, or numeric IDs)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Owner {
+    pub user: String,
+    pub group: String,
+}
+
+impl Owner {
+    /// Numeric user ID, if the user was given as a number
+    #[must_use]
+    pub fn uid(&self) -> Option<u64> {
+        self.user.parse().ok()
+    }
+
+    /// Numeric group ID, if the group was given as a number
+    #[must_use]
+    pub fn gid(&self) -> Option<u64> {
+        self.group.parse().ok()
+    }
+
+    /// Named accounts may not exist until a maintainer script (or another package) creates them,
+    /// so ownership is re-applied in postinst.
+    #[must_use]
+    pub fn needs_chown(&self) -> bool {
+        let is_named = |name: &str| name != "root" && name.parse::<u64>().is_err();
+        is_named(&self.user) || is_named(&self.group)
+    }
+}
+
+impl FromStr for Owner {
+    type Err = CargoDebError;
+
+    fn from_str(s: &str) -> CDResult<Self> {
+        let (user, group) = s.split_once(':').unwrap_or((s, "root"));
+        let is_valid = |name: &str| !name.is_empty() && name.bytes().all(|c| c.is_ascii_alphanumeric() || b"._-".contains(&c));
+        if !is_valid(user) || !is_valid(group) {
+            return Err(CargoDebError::InvalidOwner(s.into()));
+        }
+        Ok(Self { user: user.into(), group: group.into() })
+    }
+}
+
+impl fmt::Display for Owner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.user, self.group)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Asset {
     pub source: AssetSource,
@@ -194,7 +261,10 @@ impl Asset {
     #[must_use]
     pub fn new(source: AssetSource, mut target_path: PathBuf, chmod: u32, is_built: IsBuilt) -> Self {
         // is_dir() is only for paths that exist
-        if target_path.to_string_lossy().ends_with('/') {
+        if source.is_dir() {
+            // drop the trailing slash
+            target_path = target_path.components().collect();
+        } else if target_path.to_string_lossy().ends_with('/') {
             let file_name = source.path().and_then(|p| p.file_name()).expect("source must be a file");
             target_path = target_path.join(file_name);
         }
@@ -206,10 +276,16 @@ impl Asset {
         Self {
             source,
             c: AssetCommon {
-                target_path, chmod, is_built,
+                target_path, chmod, owner: None, is_built,
             },
         }
     }
+
+    #[must_use]
+    pub fn with_owner(mut self, owner: Option<Owner>) -> Self {
+        self.c.owner = owner;
+        self
+    }
 }
 
 impl AssetCommon {
@@ -245,6 +321,31 @@ impl AssetCommon {
     }
 }
 
+/// Location where gdb, debuginfod and systemd-coredump look up debug info by the GNU build ID:
+/// `/usr/lib/debug/.build-id/xx/yyyy.debug`, where `xx` are the first two hex digits of the ID.
+#[must_use]
+pub fn build_id_debug_target(build_id: &str) -> Option<PathBuf> {
+    if build_id.len() < 3 || !build_id.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let (dir, file) = build_id.split_at(2);
+    Some(Path::new("/usr/lib/debug/.build-id").join(dir).join(format!("{file}.debug")))
+}
+
+/// Relative path to use as a target of a symlink at `link` that points to `target`.
+/// Both paths are in the package, relative to the same root.
+pub(crate) fn relative_symlink_target(link: &Path, target: &Path) -> PathBuf {
+    let link_dir: Vec<_> = link.parent().map(|p| p.components().collect()).unwrap_or_default();
+    let target: Vec<_> = target.components().collect();
+    let common = link_dir.iter().zip(&target).take_while(|(a, b)| a == b).count();
+    let mut relative = PathBuf::new();
+    for _ in common..link_dir.len() {
+        relative.push("..");
+    }
+    relative.extend(&target[common..]);
+    relative
+}
+
 /// Adds `.debug` to the end of a path to a filename
 ///
 fn debug_filename(path: &Path) -> PathBuf {
@@ -253,50 +354,7 @@ fn debug_filename(path: &Path) -> PathBuf {
     Path::new(&debug_filename).to_path_buf()
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
-enum ArchSpec {
-    /// e.g. [armhf]
-    Require(String),
-    /// e.g. [!armhf]
-    NegRequire(String),
-}
-
-fn get_architecture_specification(depend: &str) -> CDResult<(String, Option<ArchSpec>)> {
-    use ArchSpec::*;
-    let re = regex::Regex::new(r#"(.*)\[(!?)(.*)\]"#).unwrap();
-    match re.captures(depend) {
-        Some(caps) => {
-            let spec = if &caps[2] == "!" {
-                NegRequire(caps[3].to_string())
-            } else {
-                assert_eq!(&caps[2], "");
-                Require(caps[3].to_string())
-            };
-            Ok((caps[1].trim().to_string(), Some(spec)))
-        }
-        None => Ok((depend.to_string(), None)),
-    }
-}
-
-/// Architecture specification strings
-/// <https://www.debian.org/doc/debian-policy/ch-customized-programs.html#s-arch-spec>
-fn match_architecture(spec: ArchSpec, target_arch: &str) -> CDResult<bool> {
-    let (neg, spec) = match spec {
-        ArchSpec::NegRequire(pkg) => (true, pkg),
-        ArchSpec::Require(pkg) => (false, pkg),
-    };
-    let output = Command::new("dpkg-architecture")
-        .args(["-a", target_arch, "-i", &spec])
-        .output()
-        .map_err(|e| CargoDebError::CommandFailed(e, "dpkg-architecture"))?;
-    if neg {
-        Ok(!output.status.success())
-    } else {
-        Ok(output.status.success())
-    }
-}
-
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 #[non_exhaustive]
 /// Cargo deb configuration read from the manifest and cargo metadata
 pub struct Config {
@@ -308,6 +366,8 @@ pub struct Config {
     pub target: Option<String>,
     /// `CARGO_TARGET_DIR`
     pub target_dir: PathBuf,
+    /// Root directory of the target system, used to find its libraries, pkg-config files and binutils
+    pub sysroot: Option<PathBuf>,
     /// The name of the project to build
     pub name: String,
     /// The name to give the Debian package; usually the same as the Cargo project name
@@ -371,6 +431,22 @@ pub struct Config {
 
     /// The Debian architecture of the target system.
     pub architecture: String,
+    /// Whether the package can be co-installed with, or used by packages of, other architectures
+    pub multi_arch: Option<MultiArch>,
+    /// Whether to list statically linked crates in the `Static-Built-Using` field
+    pub static_built_using: bool,
+    /// Whether to add `Files` paragraphs of the linked crates to the `copyright` file
+    pub copyright_dependencies: bool,
+    /// Whether to install license files of the linked crates in `usr/share/doc/<deb_name>/third-party`
+    pub third_party_licenses: bool,
+    /// SBOMs to write next to the `.deb` file
+    pub sbom: Vec<SbomFormat>,
+    /// Whether to also install the SBOMs in `usr/share/doc/<deb_name>/`
+    pub install_sbom: bool,
+    /// Crates from registries and git that are linked into the binaries
+    pub(crate) linked_crates: Vec<LinkedCrate>,
+    /// Additional fields of the `control` file, with validated names
+    pub extra_fields: BTreeMap<String, String>,
     /// A list of configuration files installed by the package.
     pub conf_files: Option<String>,
     /// All of the files that are to be packaged.
@@ -386,10 +462,31 @@ pub struct Config {
     pub debug_enabled: bool,
     /// Should the debug symbols be moved to a separate file included in the package? (implies `strip:true`)
     pub separate_debug_symbols: bool,
+    /// Strip with the `strip` and `objcopy` commands instead of the built-in ELF stripper
+    pub system_strip: bool,
+    /// Reuse `$auto` dependencies of unchanged binaries from previous runs
+    pub dep_cache: bool,
+    /// Keep a symlink at the path-based `/usr/lib/debug/<path>.debug` location when debug info is installed by build ID
+    pub debug_path_symlinks: bool,
+    /// glibc and GCC versions of the oldest distro the package is meant for, to warn about binaries needing newer ones
+    pub(crate) baseline: Option<Baseline>,
+    /// Compress DWARF sections of the separated debug files
+    pub compress_debug_symbols: Option<DebugSectionsCompression>,
+    /// Should the separated debug symbols go into a `-dbgsym` package instead? (implies `separate_debug_symbols`)
+    pub dbgsym: bool,
+    /// `Package-Type` control field. `None` means a regular `deb`.
+    pub package_type: Option<String>,
+    /// GNU build IDs of the stripped binaries, listed in the `Build-Ids` field of the `-dbgsym` package
+    pub build_ids: Vec<String>,
     /// Should symlinks be preserved in the assets
     pub preserve_symlinks: bool,
     /// Details of how to install any systemd units
     pub(crate) systemd_units: Option<SystemdUnitsConfig>,
+    /// How to compress the control and data archives
+    pub compression: CompressConfig,
+    /// Fixed modification time of all files in the package, for reproducible builds.
+    /// Taken from `SOURCE_DATE_EPOCH` unless overridden.
+    pub source_date_epoch: Option<u64>,
 }
 
 impl Config {
@@ -397,7 +494,7 @@ impl Config {
     ///
     /// `None` target means the host machine's architecture.
     pub fn from_manifest(manifest_path: &Path, package_name: Option<&str>, output_path: Option<String>, target: Option<&str>, variant: Option<&str>, deb_version: Option<String>, deb_revision: Option<String>, listener: &dyn Listener, selected_profile: &str) -> CDResult<Config> {
-        let metadata = cargo_metadata(manifest_path)?;
+        let metadata = cargo_metadata(manifest_path, &[])?;
         let available_package_names = || {
             metadata.packages.iter()
                 .filter(|p| metadata.workspace_members.iter().any(|w| w == &p.id))
@@ -430,7 +527,34 @@ impl Config {
         }
         manifest.complete_from_path(manifest_path)
             .map_err(move |e| CargoDebError::TomlParsing(e, manifest_path.to_path_buf()))?;
-        Self::from_manifest_inner(manifest, workspace_root_manifest.as_ref(), target_package, package_manifest_dir, output_path, target_dir, target, variant, deb_version, deb_revision, listener, selected_profile)
+        let mut config = Self::from_manifest_inner(manifest, workspace_root_manifest.as_ref(), target_package, package_manifest_dir, output_path, target_dir, target, variant, deb_version, deb_revision, listener, selected_profile)?;
+        if config.needs_linked_crates() {
+            // Dependencies of other platforms and disabled optional dependencies aren't linked
+            let mut flags = vec!["--filter-platform".to_owned(), target.unwrap_or(crate::DEFAULT_TARGET).to_owned()];
+            flags.extend(config.cargo_feature_flags());
+            let metadata = cargo_metadata(manifest_path, &flags)?;
+            config.linked_crates = metadata.statically_linked_crates(&target_package.id);
+            config.add_third_party_license_assets()?;
+        }
+        Ok(config)
+    }
+
+    /// Whether any setting uses the crates statically linked into the package
+    fn needs_linked_crates(&self) -> bool {
+        self.static_built_using || self.copyright_dependencies || self.third_party_licenses || !self.sbom.is_empty()
+    }
+
+    /// `--no-default-features` and `--features` flags for Cargo
+    pub(crate) fn cargo_feature_flags(&self) -> Vec<String> {
+        let mut flags = Vec::new();
+        if !self.default_features {
+            flags.push("--no-default-features".to_owned());
+        }
+        if !self.features.is_empty() {
+            flags.push("--features".to_owned());
+            flags.push(self.features.join(","));
+        }
+        flags
     }
 
     /// Convert Cargo.toml/metadata information into internal config structure
@@ -493,6 +617,7 @@ impl Config {
             deb_output_path,
             target: target.map(|t| t.to_string()),
             target_dir,
+            sysroot: deb.sysroot.take().map(|sysroot| package_manifest_dir.join(sysroot)),
             name: package.name.clone(),
             deb_name: deb.name.take().unwrap_or_else(|| package.name.clone()),
             deb_version: deb_version.unwrap_or_else(|| manifest_version_string(package, deb_revision.or(deb.revision))),
@@ -526,6 +651,16 @@ impl Config {
             section: deb.section.take(),
             priority: deb.priority.take().unwrap_or_else(|| "optional".to_owned()),
             architecture: get_arch(target.unwrap_or(crate::DEFAULT_TARGET)).to_owned(),
+            multi_arch: deb.multi_arch.take(),
+            static_built_using: deb.static_built_using.unwrap_or(false),
+            copyright_dependencies: deb.copyright_dependencies.unwrap_or(false),
+            third_party_licenses: deb.third_party_licenses.unwrap_or(false),
+            sbom: deb.sbom.take().unwrap_or_default(),
+            install_sbom: deb.install_sbom.unwrap_or(false),
+            linked_crates: Vec::new(),
+            extra_fields: deb.extra_fields.take().unwrap_or_default().into_iter()
+                .map(|(name, value)| Ok((deb822::check_extra_field(&name, &value)?, value)))
+                .collect::<CDResult<_>>()?,
             conf_files: deb.conf_files.map(|x| format_conffiles(&x)),
             assets: Assets::new(),
             triggers_file: deb.triggers_file.map(PathBuf::from),
@@ -533,12 +668,28 @@ impl Config {
             maintainer_scripts: deb.maintainer_scripts.map(PathBuf::from),
             features: deb.features.take().unwrap_or_default(),
             default_features: deb.default_features.unwrap_or(true),
-            separate_debug_symbols: deb.separate_debug_symbols.unwrap_or(false),
+            separate_debug_symbols: deb.separate_debug_symbols.unwrap_or(false) || deb.dbgsym.unwrap_or(false),
+            dbgsym: deb.dbgsym.unwrap_or(false),
+            debug_path_symlinks: deb.debug_path_symlinks.unwrap_or(false),
+            system_strip: false,
+            dep_cache: true,
+            baseline: deb.baseline.take().map(BaselineSetting::into_baseline).transpose()?,
+            compress_debug_symbols: deb.compress_debug_symbols.take().and_then(DebugCompressionSetting::method),
+            package_type: None,
+            build_ids: Vec::new(),
             debug_enabled,
             preserve_symlinks: deb.preserve_symlinks.unwrap_or(false),
             systemd_units: deb.systemd_units.take(),
+            compression: deb.compression.take().map(CompressConfig::from).unwrap_or_default(),
+            source_date_epoch: source_date_epoch_from_env()?,
         };
+        config.check_relations()?;
+        config.check_license(listener);
+        if let (None, Some(target)) = (&config.sysroot, target) {
+            config.sysroot = config.cargo_config()?.and_then(|conf| conf.sysroot(target));
+        }
         config.take_assets(package, deb.assets.take(), &cargo_metadata.targets, selected_profile)?;
+        config.take_dirs(deb.dirs.take())?;
         config.add_copyright_asset()?;
         config.add_changelog_asset()?;
         config.add_systemd_assets()?;
@@ -547,37 +698,177 @@ impl Config {
     }
 
     pub(crate) fn get_dependencies(&self, listener: &dyn Listener) -> CDResult<String> {
-        let mut deps = HashSet::new();
+        let mut deps = RelationList::default();
+        let has_auto = self.depends.split(',').any(|word| word.trim() == "$auto");
+        let requirements = if has_auto || self.baseline.is_some() {
+            self.symbol_version_requirements(listener)
+        } else {
+            SymbolVersionRequirements::default()
+        };
         for word in self.depends.split(',') {
-            let word = word.trim();
-            if word == "$auto" {
-                let bin = self.all_binaries();
-                let resolved = bin.par_iter()
-                    .filter(|bin| !bin.archive_as_symlink_only())
-                    .filter_map(|p| p.path())
-                    .filter_map(|bname| match resolve(bname, &self.target) {
-                        Ok(bindeps) => Some(bindeps),
-                        Err(err) => {
-                            listener.warning(format!("{} (no auto deps for {})", err, bname.display()));
-                            None
-                        },
-                    })
-                    .collect::<Vec<_>>();
-                for dep in resolved.into_iter().flat_map(|s| s.into_iter()) {
-                    deps.insert(dep);
+            if word.trim() == "$auto" {
+                let resolved = self.auto_dependencies(listener);
+                // The dpkg database may be from a different distro release than the target, but symbol versions are exact
+                let glibc = requirements.glibc.as_ref().map(|glibc| format!("libc6 (>= {glibc})"));
+                for dep in merge_dependencies(resolved.into_iter().chain(glibc).collect())? {
+                    deps.extend(RelationList::parse(RelationField::Depends, &dep)?);
                 }
             } else {
-                let (dep, arch_spec) = get_architecture_specification(word)?;
-                if let Some(spec) = arch_spec {
-                    if match_architecture(spec, &self.architecture)? {
-                        deps.insert(dep);
+                deps.extend(RelationList::parse(RelationField::Depends, word)?);
+            }
+        }
+        deps.retain_architecture(&self.architecture);
+        deps.normalize(RelationField::Depends);
+        Ok(deps.to_string())
+    }
+
+    /// Normalized value of a relationship field other than `Depends`, with architecture restrictions applied
+    pub(crate) fn get_relations(&self, field: RelationField, value: &str) -> CDResult<String> {
+        let mut relations = RelationList::parse(field, value)?;
+        relations.retain_architecture(&self.architecture);
+        relations.normalize(field);
+        Ok(relations.to_string())
+    }
+
+    /// Reports syntax errors in relationship fields early, before the build
+    fn check_relations(&self) -> CDResult<()> {
+        for word in self.depends.split(',').filter(|word| word.trim() != "$auto") {
+            RelationList::parse(RelationField::Depends, word)?;
+        }
+        for (field, value) in self.relation_fields() {
+            RelationList::parse(field, value)?;
+        }
+        Ok(())
+    }
+
+    /// The `license` is translated for the `copyright` file if it's a valid SPDX expression.
+    /// Otherwise it's copied as-is, like before, so that unusual licenses don't break existing packages.
+    fn check_license(&self, listener: &dyn Listener) {
+        if let Some(license) = &self.license {
+            match LicenseExpr::parse(license) {
+                Ok(parsed) => {
+                    let unknown = parsed.unknown_ids();
+                    if !unknown.is_empty() {
+                        listener.warning(format!("license '{license}' has unrecognized SPDX identifiers: {}. See https://spdx.org/licenses/", unknown.join(", ")));
                     }
-                } else {
-                    deps.insert(dep);
+                },
+                Err(reason) => {
+                    listener.warning(format!("license '{license}' is not a valid SPDX expression ({reason}). It will be copied to the copyright file as-is"));
+                },
+            }
+        }
+    }
+
+    /// All relationship fields except `Depends`, which has `$auto`
+    pub(crate) fn relation_fields(&self) -> impl Iterator<Item = (RelationField, &str)> {
+        [
+            (RelationField::PreDepends, &self.pre_depends),
+            (RelationField::Recommends, &self.recommends),
+            (RelationField::Suggests, &self.suggests),
+            (RelationField::Enhances, &self.enhances),
+            (RelationField::Conflicts, &self.conflicts),
+            (RelationField::Breaks, &self.breaks),
+            (RelationField::Replaces, &self.replaces),
+            (RelationField::Provides, &self.provides),
+        ].into_iter().filter_map(|(field, value)| Some((field, value.as_deref()?)))
+    }
+
+    /// Dependencies of all binaries, looked up in the dpkg database, or cached from a previous run
+    fn auto_dependencies(&self, listener: &dyn Listener) -> Vec<String> {
+        // e.g. a cdylib in the same package
+        let package_files: HashSet<&str> = self.assets.resolved.iter()
+            .filter_map(|asset| asset.c.target_path.file_name()?.to_str())
+            .collect();
+        let cache = if self.dep_cache {
+            Some(DependencyCache::new(self.default_deb_output_dir().join("dep-cache"), self.target.as_deref(), &self.architecture, self.sysroot.as_deref(), &package_files))
+        } else {
+            None
+        };
+        let bin = self.all_binaries();
+        let cached = bin.par_iter()
+            .filter(|bin| !bin.source.archive_as_symlink_only())
+            .filter_map(|bin| bin.source.path())
+            .map(|bname| {
+                let key = cache.as_ref().and_then(|cache| {
+                    cache.key(bname).map_err(|err| log::debug!("not caching deps of {}: {err}", bname.display())).ok()
+                });
+                let deps = key.as_ref().and_then(|key| cache.as_ref()?.get(key));
+                (bname, key, deps)
+            })
+            .collect::<Vec<_>>();
+
+        // Loading the database is slow, and only needed for binaries that aren't cached
+        let shlibs = if cached.iter().any(|(_, _, deps)| deps.is_none()) {
+            // Without dpkg database, fall back to dpkg-shlibdeps, which will probably fail too, but with a better error message
+            ShlibsDatabase::load(self.sysroot.as_deref().unwrap_or(Path::new("/")), &self.architecture)
+                .map_err(|err| log::debug!("using dpkg-shlibdeps, because {err}")).ok()
+        } else {
+            None
+        };
+        cached.into_par_iter()
+            .filter_map(|(bname, key, deps)| {
+                if let Some(deps) = deps {
+                    log::debug!("cached deps of {}: {deps:?}", bname.display());
+                    return Some(deps);
+                }
+                let dpkg_shlibdeps = || resolve(bname, &self.target, self.sysroot.as_deref());
+                // Incomplete results aren't cached
+                let resolved = match &shlibs {
+                    Some(db) => db.resolve(bname, &package_files).map(|(bindeps, missing)| {
+                        if missing.is_empty() {
+                            return (bindeps, true);
+                        }
+                        // dpkg-shlibdeps may find them elsewhere, but if it can't, the dependencies that were found are still needed
+                        match dpkg_shlibdeps() {
+                            Ok(bindeps) => (bindeps, true),
+                            Err(err) => {
+                                listener.warning(format!("{}, and dpkg-shlibdeps failed: {err} (auto deps for {} may be incomplete)",
+                                    CargoDebError::DependencyNotFound(missing.join(", ")), bname.display()));
+                                (bindeps, false)
+                            },
+                        }
+                    }),
+                    None => dpkg_shlibdeps().map(|bindeps| (bindeps, true)),
+                };
+                match resolved {
+                    Ok((bindeps, complete)) => {
+                        if let (true, Some(cache), Some(key)) = (complete, &cache, &key) {
+                            cache.insert(key, &bindeps);
+                        }
+                        Some(bindeps)
+                    },
+                    Err(err) => {
+                        listener.warning(format!("{} (no auto deps for {})", err, bname.display()));
+                        None
+                    },
                 }
+            })
+            .flatten()
+            .collect()
+    }
+
+    /// Highest glibc and libgcc symbol versions used by the binaries.
+    /// Warns about binaries that won't run on the `baseline` distro.
+    fn symbol_version_requirements(&self, listener: &dyn Listener) -> SymbolVersionRequirements {
+        let mut requirements = SymbolVersionRequirements::default();
+        for bin in self.all_binaries().into_iter().filter(|bin| !bin.source.archive_as_symlink_only()) {
+            let path = match bin.source.path() {
+                Some(path) => path,
+                None => continue,
+            };
+            match SymbolVersionRequirements::of_binary(path) {
+                Ok(req) => {
+                    if let Some(baseline) = &self.baseline {
+                        for problem in req.exceeding(baseline) {
+                            listener.warning(format!("{} requires {problem}", bin.c.target_path.display()));
+                        }
+                    }
+                    requirements.merge(&req);
+                },
+                Err(err) => log::debug!("can't read symbol versions of {}: {err}", path.display()),
             }
         }
-        Ok(deps.into_iter().collect::<Vec<_>>().join(", "))
+        requirements
     }
 
     pub fn extend_cargo_build_flags(&self, flags: &mut Vec<String>) {
@@ -628,7 +919,7 @@ impl Config {
     }
 
     pub fn resolve_assets(&mut self) -> CDResult<()> {
-        for UnresolvedAsset { source_path, c: AssetCommon { target_path, chmod, is_built } } in self.assets.unresolved.drain(..) {
+        for UnresolvedAsset { source_path, c: AssetCommon { target_path, chmod, owner, is_built } } in self.assets.unresolved.drain(..) {
             let source_prefix: PathBuf = source_path.iter()
                 .take_while(|part| !is_glob_pattern(part.as_ref()))
                 .collect();
@@ -665,7 +956,7 @@ impl Config {
                     target_file,
                     chmod,
                     is_built,
-                ));
+                ).with_owner(owner.clone()));
             }
         }
 
@@ -685,24 +976,146 @@ impl Config {
         Ok(())
     }
 
-    pub fn add_debug_assets(&mut self) {
-        let mut assets_to_add: Vec<Asset> = Vec::new();
-        for asset in self.built_binaries_mut().into_iter().filter(|a| a.source.path().is_some()) {
-            let debug_source = asset.source.debug_source().expect("debug asset");
-            if debug_source.exists() {
-                log::debug!("added debug file {}", debug_source.display());
-                let debug_target = asset.c.debug_target().expect("debug asset");
-                assets_to_add.push(Asset::new(
-                    AssetSource::Path(debug_source),
-                    debug_target,
-                    0o644,
-                    IsBuilt::No,
-                ));
+    /// Installs license and notice files of the linked crates in `usr/share/doc/<deb_name>/third-party/<crate>/`.
+    /// Files identical to one already added become symlinks to it.
+    fn add_third_party_license_assets(&mut self) -> CDResult<()> {
+        if !self.third_party_licenses {
+            return Ok(());
+        }
+        let doc_dir = Path::new("usr/share/doc").join(&self.deb_name).join("third-party");
+        let mut added: HashMap<[u8; 16], PathBuf> = HashMap::new();
+        for c in &self.linked_crates {
+            // only multiple versions of the same crate need the version to tell them apart
+            let dir_name = if self.linked_crates.iter().filter(|other| other.name == c.name).count() > 1 {
+                format!("{}-{}", c.name, c.version)
             } else {
-                log::debug!("no debug file {}", debug_source.display());
+                c.name.clone()
+            };
+            for path in crate::dep5::license_files(c) {
+                let file_name = match path.file_name() {
+                    Some(name) => name,
+                    None => continue,
+                };
+                let data = fs::read(&path).map_err(|e| CargoDebError::IoFile("unable to read license file", e, path.clone()))?;
+                let target_path = doc_dir.join(&dir_name).join(file_name);
+                let hash = md5::compute(&data).0;
+                let source = match added.get(&hash) {
+                    Some(existing) => AssetSource::SymlinkTo(Path::new("..").join(existing.strip_prefix(&doc_dir).unwrap_or(existing))),
+                    None => {
+                        added.insert(hash, target_path.clone());
+                        AssetSource::Data(data)
+                    },
+                };
+                log::debug!("added third-party license {}", target_path.display());
+                self.assets.resolved.push(Asset::new(source, target_path, 0o644, IsBuilt::No));
             }
         }
-        self.assets.resolved.append(&mut assets_to_add);
+        Ok(())
+    }
+
+    /// Adds `.debug` files extracted from the built binaries by `strip_binaries`
+    pub fn add_debug_assets(&mut self, mut debug_assets: Vec<Asset>) {
+        for asset in &debug_assets {
+            log::debug!("added debug file {}", asset.source.path().unwrap_or(Path::new("?")).display());
+        }
+        self.assets.resolved.append(&mut debug_assets);
+        self.sort_assets_by_type();
+    }
+
+    /// Moves the `.debug` files out of this package into a new `<name>-dbgsym` package,
+    /// the way `dh_strip` makes automatic debug symbol packages (`.ddeb`).
+    ///
+    /// Returns `None` if there are no debug files to move.
+    pub fn split_dbgsym_package(&mut self) -> Option<Config> {
+        let (debug_assets, assets) = std::mem::take(&mut self.assets.resolved).into_iter()
+            .partition::<Vec<_>, _>(|asset| asset.c.target_path.starts_with("usr/lib/debug"));
+        self.assets.resolved = assets;
+        if debug_assets.is_empty() {
+            return None;
+        }
+
+        // `-o` may name the .deb file, but the .ddeb needs its own name
+        let deb_output_path = self.deb_output_path.as_ref().map(|path| {
+            if path.ends_with('/') || Path::new(path).is_dir() {
+                path.clone()
+            } else {
+                let dir = Path::new(path).parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+                format!("{}/", dir.display())
+            }
+        });
+
+        Some(Config {
+            deb_name: format!("{}-dbgsym", self.deb_name),
+            deb_output_path,
+            description: format!("debug symbols for {}", self.deb_name),
+            extended_description: None,
+            depends: format!("{} (= {})", self.deb_name, self.deb_version),
+            pre_depends: None,
+            recommends: None,
+            suggests: None,
+            enhances: None,
+            conflicts: None,
+            breaks: None,
+            replaces: None,
+            provides: None,
+            // dh_gencontrol does the same, since debug files are installed by build ID
+            multi_arch: self.multi_arch.filter(|&m| m == MultiArch::Same),
+            static_built_using: false,
+            copyright_dependencies: false,
+            third_party_licenses: false,
+            sbom: Vec::new(),
+            install_sbom: false,
+            linked_crates: Vec::new(),
+            extra_fields: BTreeMap::new(),
+            section: Some("debug".into()),
+            priority: "optional".into(),
+            conf_files: None,
+            assets: Assets::with_resolved_assets(debug_assets),
+            triggers_file: None,
+            maintainer_scripts: None,
+            systemd_units: None,
+            package_type: Some("ddeb".into()),
+            build_ids: std::mem::take(&mut self.build_ids),
+            ..self.clone()
+        })
+    }
+
+    /// Value of the `Static-Built-Using` field, with crates named after Debian's `rust-*` source packages
+    pub(crate) fn static_built_using(&self) -> Option<String> {
+        if !self.static_built_using || self.linked_crates.is_empty() {
+            return None;
+        }
+        Some(self.linked_crates.iter().map(|c| {
+            // Debian sorts `~` before releases, like semver's pre-release versions. Build metadata is not part of the version.
+            let version = c.version.split('+').next().unwrap_or_default().replacen('-', "~", 1);
+            format!("rust-{} (= {version})", c.name.replace('_', "-").to_ascii_lowercase())
+        }).collect::<Vec<_>>().join(", "))
+    }
+
+    /// Debian's multiarch tuple of the target, e.g. `x86_64-linux-gnu`, used in library paths
+    pub(crate) fn multiarch_triple(&self) -> String {
+        crate::debian_triple(self.target.as_deref().unwrap_or(crate::DEFAULT_TARGET))
+    }
+
+    /// Files that would differ between architectures, but aren't in architecture-specific directories,
+    /// so `Multi-Arch: same` packages of different architectures couldn't be installed together.
+    pub(crate) fn multi_arch_conflicts(&self) -> Vec<&Path> {
+        if self.multi_arch != Some(MultiArch::Same) {
+            return Vec::new();
+        }
+        let multiarch = self.multiarch_triple();
+        self.assets.resolved.iter()
+            .filter(|asset| !asset.source.is_dir())
+            .filter(|asset| {
+                let path = &asset.c.target_path;
+                if path.iter().any(|dir| dir == multiarch.as_str()) || path.starts_with("usr/lib/debug/.build-id") {
+                    return false;
+                }
+                let is_executable_dir = ["bin", "sbin", "usr/bin", "usr/sbin", "usr/games"].iter().any(|dir| path.parent() == Some(Path::new(dir)));
+                is_executable_dir || asset.c.is_built != IsBuilt::No
+            })
+            .map(|asset| asset.c.target_path.as_path())
+            .collect()
     }
 
     fn add_changelog_asset(&mut self) -> CDResult<()> {
@@ -748,13 +1161,12 @@ impl Config {
     }
 
     /// Executables AND dynamic libraries. May include symlinks.
-    fn all_binaries(&self) -> Vec<&AssetSource> {
+    fn all_binaries(&self) -> Vec<&Asset> {
         self.assets.resolved.iter()
             .filter(|asset| {
                 // Assumes files in build dir which have executable flag set are binaries
-                asset.c.is_dynamic_library() || asset.c.is_executable()
+                !asset.source.is_dir() && (asset.c.is_dynamic_library() || asset.c.is_executable())
             })
-            .map(|asset| &asset.source)
             .collect()
     }
 
@@ -811,7 +1223,7 @@ impl Config {
     }
 
     /// Store intermediate files here
-    pub(crate) fn deb_temp_dir(&self) -> PathBuf {
+    pub fn deb_temp_dir(&self) -> PathBuf {
         self.target_dir.join("debian").join(&self.name)
     }
 
@@ -844,10 +1256,22 @@ impl Config {
             .then(a.c.is_dynamic_library().cmp(&b.c.is_dynamic_library()))
             .then(a.c.target_path.extension().cmp(&b.c.target_path.extension()))
             .then(a.c.target_path.parent().cmp(&b.c.target_path.parent()))
+            // the order of files in the archive must not depend on the order they were found in
+            .then(a.c.target_path.cmp(&b.c.target_path))
         });
     }
 }
 
+/// <https://reproducible-builds.org/specs/source-date-epoch/>
+fn source_date_epoch_from_env() -> CDResult<Option<u64>> {
+    match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(epoch) if !epoch.trim().is_empty() => {
+            Ok(Some(epoch.trim().parse().map_err(|e| CargoDebError::NumParse("invalid SOURCE_DATE_EPOCH", e))?))
+        },
+        _ => Ok(None),
+    }
+}
+
 fn debug_flag(manifest: &cargo_toml::Manifest<CargoPackageMetadata>) -> bool {
     manifest.profile.release.as_ref()
         .and_then(|r| r.debug.as_ref())
@@ -928,10 +1352,11 @@ fn take_assets(&mut self, package: &cargo_toml::Package<CargoPackageMetadata>, a
             let target_path = PathBuf::from(asset_parts.next().ok_or("missing target (second array entry) for asset in Cargo.toml. Use something like \"usr/local/bin/\".")?);
             let chmod = u32::from_str_radix(&asset_parts.next().ok_or("missing chmod (third array entry) for asset in Cargo.toml. Use an octal string like \"777\".")?, 8)
                 .map_err(|e| CargoDebError::NumParse("unable to parse chmod argument", e))?;
+            let owner = asset_parts.next().map(|owner| owner.parse()).transpose()?;
 
             unresolved_assets.push(UnresolvedAsset {
                 source_path,
-                c: AssetCommon { target_path, chmod, is_built },
+                c: AssetCommon { target_path, chmod, owner, is_built },
             })
         }
         Assets::with_unresolved_assets(unresolved_assets)
@@ -948,9 +1373,14 @@ fn take_assets(&mut self, package: &cargo_toml::Package<CargoPackageMetadata>, a
                 } else if t.crate_types.iter().any(|ty| ty == "cdylib") && t.kind.iter().any(|k| k == "cdylib") {
                     // FIXME: std has constants for the host arch, but not for cross-compilation
                     let lib_name = format!("{DLL_PREFIX}{}{DLL_SUFFIX}", t.name);
+                    let lib_dir = if self.multi_arch.is_some() {
+                        Path::new("usr/lib").join(self.multiarch_triple())
+                    } else {
+                        PathBuf::from("usr/lib")
+                    };
                     Some(Asset::new(
                         AssetSource::Path(self.path_in_build(&lib_name, profile)),
-                        Path::new("usr/lib").join(lib_name),
+                        lib_dir.join(lib_name),
                         0o644,
                         self.is_built_file_in_package(t.name.as_ref(), build_targets),
                     ))
@@ -974,6 +1404,27 @@ fn take_assets(&mut self, package: &cargo_toml::Package<CargoPackageMetadata>, a
     self.assets = assets;
     Ok(())
 }
+
+/// Directories are created with the given mode and owner, even if they're empty
+fn take_dirs(&mut self, dirs: Option<Vec<Vec<String>>>) -> CDResult<()> {
+    for mut dir_line in dirs.unwrap_or_default() {
+        let mut dir_parts = dir_line.drain(..);
+        let target_path = PathBuf::from(dir_parts.next()
+            .ok_or("missing path (first array entry) for directory in Cargo.toml")?);
+        let chmod = u32::from_str_radix(&dir_parts.next().ok_or("missing chmod (second array entry) for directory in Cargo.toml. Use an octal string like \"755\".")?, 8)
+            .map_err(|e| CargoDebError::NumParse("unable to parse chmod argument", e))?;
+        let owner = dir_parts.next().map(|owner| owner.parse()).transpose()?;
+        let asset = Asset::new(AssetSource::Dir, target_path, chmod, IsBuilt::No).with_owner(owner);
+        if asset.c.target_path.as_os_str().is_empty() {
+            return Err("the root directory can't be a directory asset in Cargo.toml".into());
+        }
+        if self.assets.resolved.iter().any(|a| a.source.is_dir() && a.c.target_path == asset.c.target_path) {
+            return Err(CargoDebError::DuplicateDirectory(asset.c.target_path));
+        }
+        self.assets.resolved.push(asset);
+    }
+    Ok(())
+}
     fn is_built_file_in_package(&self, rel_path: &Path, build_targets: &[CargoMetadataTarget]) -> IsBuilt {
         let source_name = rel_path.file_name().expect("asset filename").to_str().expect("utf-8 names");
         let source_name = source_name.strip_suffix(EXE_SUFFIX).unwrap_or(source_name);
@@ -1023,6 +1474,115 @@ enum LicenseFile {
     Vec(Vec<String>),
 }
 
+/// Either `compress-debug-symbols = true` (zlib) or `compress-debug-symbols = "zstd"`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+enum DebugCompressionSetting {
+    Enabled(bool),
+    Method(DebugSectionsCompression),
+}
+
+impl DebugCompressionSetting {
+    fn method(self) -> Option<DebugSectionsCompression> {
+        match self {
+            Self::Enabled(true) => Some(DebugSectionsCompression::Zlib),
+            Self::Enabled(false) => None,
+            Self::Method(method) => Some(method),
+        }
+    }
+}
+
+/// `Multi-Arch` control field
+/// <https://wiki.debian.org/Multiarch/Implementation>
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MultiArch {
+    /// Can be co-installed with the same package of other architectures
+    Same,
+    /// Can satisfy dependencies of packages of other architectures
+    Foreign,
+    /// Dependencies with `:any` can be satisfied by any architecture
+    Allowed,
+}
+
+impl fmt::Display for MultiArch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Same => "same",
+            Self::Foreign => "foreign",
+            Self::Allowed => "allowed",
+        })
+    }
+}
+
+/// Formats of software bills of materials
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum SbomFormat {
+    /// SPDX 2.3 JSON
+    #[serde(rename = "spdx")]
+    Spdx,
+    /// CycloneDX 1.5 JSON
+    #[serde(rename = "cyclonedx")]
+    CycloneDx,
+}
+
+/// Either `baseline = "debian-11"` or `baseline = { glibc = "2.31", gcc = "10.2" }`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+enum BaselineSetting {
+    Distro(String),
+    Versions(BaselineTable),
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct BaselineTable {
+    pub glibc: Option<String>,
+    pub gcc: Option<String>,
+}
+
+impl BaselineSetting {
+    fn into_baseline(self) -> CDResult<Baseline> {
+        match self {
+            Self::Distro(name) => Baseline::distro(&name),
+            Self::Versions(t) => Ok(Baseline { name: "the baseline".into(), glibc: t.glibc, gcc: t.gcc }),
+        }
+    }
+}
+
+/// Either `compression = "xz"` or `compression = { method = "xz", level = 9 }`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+enum CompressionSetting {
+    Method(Format),
+    Table(CompressionTable),
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct CompressionTable {
+    pub method: Option<Format>,
+    pub level: Option<Level>,
+    pub threads: Option<u32>,
+    pub fast: Option<bool>,
+    pub system: Option<bool>,
+}
+
+impl From<CompressionSetting> for CompressConfig {
+    fn from(setting: CompressionSetting) -> Self {
+        match setting {
+            CompressionSetting::Method(format) => Self { format, ..Self::default() },
+            CompressionSetting::Table(t) => Self {
+                format: t.method.unwrap_or_default(),
+                level: t.level,
+                threads: t.threads,
+                fast: t.fast.unwrap_or(false),
+                use_system: t.system.unwrap_or(false),
+            },
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Default)]
 #[serde(rename_all = "kebab-case", deny_unknown_fields)]
 struct CargoDeb {
@@ -1047,13 +1607,27 @@ struct CargoDeb {
     pub revision: Option<String>,
     pub conf_files: Option<Vec<String>>,
     pub assets: Option<Vec<Vec<String>>>,
+    pub dirs: Option<Vec<Vec<String>>>,
     pub triggers_file: Option<String>,
     pub maintainer_scripts: Option<String>,
     pub features: Option<Vec<String>>,
     pub default_features: Option<bool>,
     pub separate_debug_symbols: Option<bool>,
+    pub dbgsym: Option<bool>,
+    pub debug_path_symlinks: Option<bool>,
+    pub compress_debug_symbols: Option<DebugCompressionSetting>,
+    pub baseline: Option<BaselineSetting>,
+    pub sysroot: Option<String>,
+    pub multi_arch: Option<MultiArch>,
+    pub static_built_using: Option<bool>,
+    pub copyright_dependencies: Option<bool>,
+    pub third_party_licenses: Option<bool>,
+    pub sbom: Option<Vec<SbomFormat>>,
+    pub install_sbom: Option<bool>,
+    pub extra_fields: Option<BTreeMap<String, String>>,
     pub preserve_symlinks: Option<bool>,
     pub systemd_units: Option<SystemdUnitsConfig>,
+    pub compression: Option<CompressionSetting>,
     pub variants: Option<HashMap<String, CargoDeb>>,
 }
 
@@ -1081,13 +1655,38 @@ impl CargoDeb {
             revision: self.revision.or(parent.revision),
             conf_files: self.conf_files.or(parent.conf_files),
             assets: self.assets.or(parent.assets),
+            dirs: self.dirs.or(parent.dirs),
             triggers_file: self.triggers_file.or(parent.triggers_file),
             maintainer_scripts: self.maintainer_scripts.or(parent.maintainer_scripts),
             features: self.features.or(parent.features),
             default_features: self.default_features.or(parent.default_features),
             separate_debug_symbols: self.separate_debug_symbols.or(parent.separate_debug_symbols),
+            dbgsym: self.dbgsym.or(parent.dbgsym),
+            debug_path_symlinks: self.debug_path_symlinks.or(parent.debug_path_symlinks),
+            compress_debug_symbols: self.compress_debug_symbols.or(parent.compress_debug_symbols),
+            baseline: self.baseline.or(parent.baseline),
+            sysroot: self.sysroot.or(parent.sysroot),
+            multi_arch: self.multi_arch.or(parent.multi_arch),
+            static_built_using: self.static_built_using.or(parent.static_built_using),
+            copyright_dependencies: self.copyright_dependencies.or(parent.copyright_dependencies),
+            third_party_licenses: self.third_party_licenses.or(parent.third_party_licenses),
+            sbom: self.sbom.or(parent.sbom),
+            install_sbom: self.install_sbom.or(parent.install_sbom),
+            extra_fields: match (self.extra_fields, parent.extra_fields) {
+                (Some(mut fields), Some(parent_fields)) => {
+                    // field names are case-insensitive, and the variant's spelling of the name wins too
+                    for (name, value) in parent_fields {
+                        if !fields.keys().any(|n| n.eq_ignore_ascii_case(&name)) {
+                            fields.insert(name, value);
+                        }
+                    }
+                    Some(fields)
+                },
+                (fields, parent_fields) => fields.or(parent_fields),
+            },
             preserve_symlinks: self.preserve_symlinks.or(parent.preserve_symlinks),
             systemd_units: self.systemd_units.or(parent.systemd_units),
+            compression: self.compression.or(parent.compression),
             variants: self.variants.or(parent.variants),
         }
     }
@@ -1107,14 +1706,111 @@ struct CargoMetadata {
 #[derive(Deserialize)]
 struct CargoMetadataResolve {
     root: Option<String>,
+    #[serde(default)]
+    nodes: Vec<CargoMetadataNode>,
+}
+
+#[derive(Deserialize)]
+struct CargoMetadataNode {
+    id: String,
+    #[serde(default)]
+    deps: Vec<CargoMetadataNodeDep>,
+}
+
+#[derive(Deserialize)]
+struct CargoMetadataNodeDep {
+    pkg: String,
+    /// Missing in Cargo older than 1.41
+    #[serde(default)]
+    dep_kinds: Vec<CargoMetadataDepKind>,
+}
+
+#[derive(Deserialize)]
+struct CargoMetadataDepKind {
+    /// `None` for normal dependencies, `dev` or `build` otherwise
+    kind: Option<String>,
 }
 
 #[derive(Deserialize)]
 struct CargoMetadataPackage {
     pub id: String,
     pub name: String,
+    pub version: String,
+    /// `None` for path dependencies
+    pub source: Option<String>,
     pub targets: Vec<CargoMetadataTarget>,
     pub manifest_path: String,
+    #[serde(default)]
+    pub license: Option<String>,
+    #[serde(default)]
+    pub license_file: Option<PathBuf>,
+    #[serde(default)]
+    pub authors: Vec<String>,
+    #[serde(default)]
+    pub repository: Option<String>,
+}
+
+/// A dependency compiled into the package's binaries
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LinkedCrate {
+    pub name: String,
+    pub version: String,
+    /// SPDX expression
+    pub license: Option<String>,
+    /// Absolute path of the `license-file` from its `Cargo.toml`
+    pub license_file: Option<PathBuf>,
+    pub authors: Vec<String>,
+    pub repository: Option<String>,
+    /// Directory with the crate's `Cargo.toml`
+    pub source_dir: PathBuf,
+}
+
+impl CargoMetadataPackage {
+    fn is_proc_macro(&self) -> bool {
+        self.targets.iter().any(|t| t.kind.iter().any(|k| k == "proc-macro"))
+    }
+}
+
+impl CargoMetadata {
+    /// Crates from registries and git that are statically linked into the package's binaries.
+    /// Dev and build dependencies and proc macros run only at build time, so they're not included.
+    fn statically_linked_crates(&self, root_id: &str) -> Vec<LinkedCrate> {
+        let nodes: HashMap<&str, &CargoMetadataNode> = self.resolve.nodes.iter().map(|n| (n.id.as_str(), n)).collect();
+        let packages: HashMap<&str, &CargoMetadataPackage> = self.packages.iter().map(|p| (p.id.as_str(), p)).collect();
+        let mut seen = HashSet::new();
+        let mut queue = vec![root_id];
+        while let Some(id) = queue.pop() {
+            let node = match nodes.get(id) {
+                Some(n) => n,
+                None => continue,
+            };
+            for dep in &node.deps {
+                let is_normal = dep.dep_kinds.is_empty() || dep.dep_kinds.iter().any(|k| k.kind.is_none());
+                let is_proc_macro = packages.get(dep.pkg.as_str()).map_or(false, |p| p.is_proc_macro());
+                if is_normal && !is_proc_macro && seen.insert(dep.pkg.as_str()) {
+                    queue.push(&dep.pkg);
+                }
+            }
+        }
+        let mut crates: Vec<_> = self.packages.iter()
+            .filter(|p| p.source.is_some() && seen.contains(p.id.as_str()))
+            .map(|p| {
+                let source_dir = Path::new(&p.manifest_path).parent().unwrap_or(Path::new(".")).to_owned();
+                LinkedCrate {
+                    name: p.name.clone(),
+                    version: p.version.clone(),
+                    license: p.license.clone(),
+                    license_file: p.license_file.as_ref().map(|f| source_dir.join(f)),
+                    authors: p.authors.clone(),
+                    repository: p.repository.clone(),
+                    source_dir,
+                }
+            })
+            .collect();
+        crates.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.version.cmp(&b.version)));
+        crates.dedup_by(|a, b| a.name == b.name && a.version == b.version);
+        crates
+    }
 }
 
 #[derive(Deserialize)]
@@ -1126,11 +1822,12 @@ struct CargoMetadataTarget {
 }
 
 /// Returns the path of the `Cargo.toml` that we want to build.
-fn cargo_metadata(manifest_path: &Path) -> CDResult<CargoMetadata> {
+fn cargo_metadata(manifest_path: &Path, flags: &[String]) -> CDResult<CargoMetadata> {
     let mut cmd = Command::new("cargo");
     cmd.arg("metadata");
     cmd.arg("--format-version=1");
     cmd.arg("--manifest-path"); cmd.arg(manifest_path);
+    cmd.args(flags);
 
     let output = cmd.output()
         .map_err(|e| CargoDebError::CommandFailed(e, "cargo (is it in your PATH?)"))?;
@@ -1145,6 +1842,14 @@ fn cargo_metadata(manifest_path: &Path) -> CDResult<CargoMetadata> {
 
 /// Debianizes the architecture name
 pub(crate) fn get_arch(target: &str) -> &str {
+    let (abi, cpu) = debian_abi_and_cpu(target);
+    DEBIAN_ARCH_NAMES.iter()
+        .find(|&&(name_abi, name_cpu, _)| name_abi == abi && name_cpu == cpu)
+        .map_or(cpu, |&(_, _, name)| name)
+}
+
+/// ABI and CPU parts of Debian's arch tuple for a Rust target
+fn debian_abi_and_cpu(target: &str) -> (&str, &str) {
     let mut parts = target.split('-');
     let arch = parts.next().unwrap();
     let abi = parts.last().unwrap_or("");
@@ -1152,26 +1857,60 @@ pub(crate) fn get_arch(target: &str) -> &str {
         // https://wiki.debian.org/Multiarch/Tuples
         // rustc --print target-list
         // https://doc.rust-lang.org/std/env/consts/constant.ARCH.html
-        ("aarch64", _) => "arm64",
-        ("mips64", "gnuabin32") => "mipsn32",
-        ("mips64el", "gnuabin32") => "mipsn32el",
-        ("mipsisa32r6", _) => "mipsr6",
-        ("mipsisa32r6el", _) => "mipsr6el",
-        ("mipsisa64r6", "gnuabi64") => "mips64r6",
-        ("mipsisa64r6", "gnuabin32") => "mipsn32r6",
-        ("mipsisa64r6el", "gnuabi64") => "mips64r6el",
-        ("mipsisa64r6el", "gnuabin32") => "mipsn32r6el",
-        ("powerpc", "gnuspe") => "powerpcspe",
-        ("powerpc64", _) => "ppc64",
-        ("powerpc64le", _) => "ppc64el",
-        ("riscv64gc", _) => "riscv64",
-        ("i586", _) | ("i686", _) | ("x86", _) => "i386",
-        ("x86_64", "gnux32") => "x32",
-        ("x86_64", _) => "amd64",
-        (arm, gnueabi) if arm.starts_with("arm") && gnueabi.ends_with("hf") => "armhf",
-        (arm, _) if arm.starts_with("arm") => "armel",
-        (other_arch, _) => other_arch,
+        ("aarch64", _) => ("base", "arm64"),
+        ("mips64", "gnuabin32") => ("abin32", "mips64"),
+        ("mips64el", "gnuabin32") => ("abin32", "mips64el"),
+        ("mips64", _) | ("mips64el", _) => ("abi64", arch),
+        ("mipsisa32r6", _) => ("base", "mipsr6"),
+        ("mipsisa32r6el", _) => ("base", "mipsr6el"),
+        ("mipsisa64r6", "gnuabi64") => ("abi64", "mips64r6"),
+        ("mipsisa64r6", "gnuabin32") => ("abin32", "mips64r6"),
+        ("mipsisa64r6el", "gnuabi64") => ("abi64", "mips64r6el"),
+        ("mipsisa64r6el", "gnuabin32") => ("abin32", "mips64r6el"),
+        ("powerpc", "gnuspe") => ("spe", "powerpc"),
+        ("powerpc64", _) => ("base", "ppc64"),
+        ("powerpc64le", _) => ("base", "ppc64el"),
+        ("riscv64gc", _) => ("base", "riscv64"),
+        ("i586", _) | ("i686", _) | ("x86", _) => ("base", "i386"),
+        ("x86_64", "gnux32") => ("x32", "amd64"),
+        ("x86_64", _) => ("base", "amd64"),
+        (arm, gnueabi) if arm.starts_with("arm") && gnueabi.ends_with("hf") => ("eabihf", "arm"),
+        (arm, _) if arm.starts_with("arm") => ("eabi", "arm"),
+        (other_arch, _) => ("base", other_arch),
+    }
+}
+
+/// Debian architectures with a non-`base` ABI, by ABI and CPU. Others are named after their CPU.
+const DEBIAN_ARCH_NAMES: [(&str, &str, &str); 13] = [
+    ("eabihf", "arm", "armhf"),
+    ("eabi", "arm", "armel"),
+    ("ilp32", "arm64", "arm64ilp32"),
+    ("x32", "amd64", "x32"),
+    ("spe", "powerpc", "powerpcspe"),
+    ("abi64", "mips64", "mips64"),
+    ("abi64", "mips64el", "mips64el"),
+    ("abi64", "mips64r6", "mips64r6"),
+    ("abi64", "mips64r6el", "mips64r6el"),
+    ("abin32", "mips64", "mipsn32"),
+    ("abin32", "mips64el", "mipsn32el"),
+    ("abin32", "mips64r6", "mipsn32r6"),
+    ("abin32", "mips64r6el", "mipsn32r6el"),
+];
+
+/// Debian's (abi, libc, os, cpu) tuple of an architecture name, like `armhf`, `hurd-i386` or `musl-linux-amd64`
+/// <https://wiki.debian.org/Multiarch/Tuples>
+pub(crate) fn debian_arch_tuple(arch: &str) -> Option<[&str; 4]> {
+    let mut parts = arch.rsplit('-');
+    let name = parts.next()?;
+    let (abi, cpu) = DEBIAN_ARCH_NAMES.iter()
+        .find(|&&(_, _, known)| known == name)
+        .map_or(("base", name), |&(abi, cpu, _)| (abi, cpu));
+    let os = parts.next().unwrap_or("linux");
+    let libc = parts.next().unwrap_or("gnu");
+    if parts.next().is_some() {
+        return None;
     }
+    Some([abi, libc, os, cpu])
 }
 
 /// Format conffiles section, ensuring each path has a leading slash
@@ -1199,19 +1938,48 @@ mod tests {
     #[test]
     fn match_arm_arch() {
         assert_eq!("armhf", get_arch("arm-unknown-linux-gnueabihf"));
+        assert_eq!("armel", get_arch("armv5te-unknown-linux-gnueabi"));
     }
 
     #[test]
-    fn arch_spec() {
-        use ArchSpec::*;
-        // req
-        assert_eq!(
-            get_architecture_specification("libjpeg64-turbo [armhf]").expect("arch"),
-            ("libjpeg64-turbo".to_owned(), Some(Require("armhf".to_owned()))));
-        // neg
-        assert_eq!(
-            get_architecture_specification("libjpeg64-turbo [!amd64]").expect("arch"),
-            ("libjpeg64-turbo".to_owned(), Some(NegRequire("amd64".to_owned()))));
+    fn arch_names() {
+        for (target, arch) in [
+            ("x86_64-unknown-linux-gnu", "amd64"),
+            ("x86_64-unknown-linux-musl", "amd64"),
+            ("x86_64-unknown-linux-gnux32", "x32"),
+            ("i686-unknown-linux-gnu", "i386"),
+            ("aarch64-unknown-linux-gnu", "arm64"),
+            ("mips64el-unknown-linux-gnuabi64", "mips64el"),
+            ("mips64-unknown-linux-gnuabin32", "mipsn32"),
+            ("mipsisa64r6el-unknown-linux-gnuabin32", "mipsn32r6el"),
+            ("powerpc-unknown-linux-gnuspe", "powerpcspe"),
+            ("powerpc64le-unknown-linux-gnu", "ppc64el"),
+            ("riscv64gc-unknown-linux-gnu", "riscv64"),
+            ("s390x-unknown-linux-gnu", "s390x"),
+        ] {
+            assert_eq!(arch, get_arch(target), "{target}");
+            assert_eq!(Some(debian_abi_and_cpu(target)), debian_arch_tuple(arch).map(|[abi, _, _, cpu]| (abi, cpu)), "{target}");
+        }
+        assert_eq!(Some(["base", "musl", "linux", "amd64"]), debian_arch_tuple("musl-linux-amd64"));
+        assert_eq!(Some(["eabihf", "gnu", "linux", "arm"]), debian_arch_tuple("linux-armhf"));
+        assert_eq!(Some(["base", "gnu", "hurd", "i386"]), debian_arch_tuple("hurd-i386"));
+        assert_eq!(None, debian_arch_tuple("a-b-c-d"));
+    }
+
+    #[test]
+    fn inherit_extra_fields() {
+        let parent = CargoDeb {
+            extra_fields: Some([("X-Foo".to_owned(), "parent".to_owned()), ("Bugs".to_owned(), "parent".to_owned())].into_iter().collect()),
+            ..CargoDeb::default()
+        };
+        let variant = CargoDeb {
+            extra_fields: Some([("x-foo".to_owned(), "variant".to_owned())].into_iter().collect()),
+            ..CargoDeb::default()
+        };
+        let fields = variant.inherit_from(parent).extra_fields.unwrap();
+        assert_eq!(2, fields.len());
+        assert_eq!("variant", fields["x-foo"]);
+        assert_eq!("parent", fields["Bugs"]);
     }
 
     #[test]
@@ -1285,6 +2053,18 @@ mod tests {
         assert_eq!(a.c.debug_target(), None);
     }
 
+    #[test]
+    fn build_id_debug_paths() {
+        let target = build_id_debug_target("7d73b44a36").unwrap();
+        assert_eq!(Path::new("/usr/lib/debug/.build-id/7d/73b44a36.debug"), target);
+        assert!(build_id_debug_target("7d").is_none());
+        assert!(build_id_debug_target("../x").is_none());
+
+        let link = Path::new("/usr/lib/debug/usr/bin/foo.debug");
+        assert_eq!(Path::new("../../.build-id/7d/73b44a36.debug"), relative_symlink_target(link, &target));
+        assert_eq!(Path::new("bar"), relative_symlink_target(Path::new("usr/bin/foo"), Path::new("usr/bin/bar")));
+    }
+
     /// Tests that debug_source() for an AssetSource::Path returns the same path
     /// but with ".debug" appended
     #[test]
@@ -1351,6 +2131,161 @@ mod tests {
         assert_eq!(1, num_unit_assets);
     }
 
+    #[test]
+    fn split_dbgsym_package() {
+        let mut mock_listener = crate::listener::MockListener::new();
+        mock_listener.expect_info().return_const(());
+        let mut config = Config::from_manifest(Path::new("Cargo.toml"), None, None, None, None, None, None, &mock_listener, "release").unwrap();
+        assert!(config.split_dbgsym_package().is_none());
+
+        let num_assets = config.assets.resolved.len();
+        config.build_ids = vec!["abcd".into()];
+        config.deb_output_path = Some("out/cargo-deb.deb".into());
+        config.add_debug_assets(vec![Asset::new(
+            AssetSource::Path(PathBuf::from("target/release/cargo-deb.debug")),
+            PathBuf::from("usr/lib/debug/usr/bin/cargo-deb.debug"),
+            0o644,
+            IsBuilt::No,
+        )]);
+
+        let dbgsym = config.split_dbgsym_package().unwrap();
+        assert_eq!(num_assets, config.assets.resolved.len());
+        assert!(config.build_ids.is_empty());
+        assert_eq!("cargo-deb-dbgsym", dbgsym.deb_name);
+        assert_eq!(format!("cargo-deb (= {})", config.deb_version), dbgsym.depends);
+        assert_eq!(Some("debug"), dbgsym.section.as_deref());
+        assert_eq!(Some("ddeb"), dbgsym.package_type.as_deref());
+        assert_eq!(["abcd"], &dbgsym.build_ids[..]);
+        assert_eq!(Some("out/"), dbgsym.deb_output_path.as_deref());
+        assert_eq!(1, dbgsym.assets.resolved.len());
+        assert!(dbgsym.maintainer_scripts.is_none());
+    }
+
+    #[test]
+    fn multi_arch_same() {
+        let mut mock_listener = crate::listener::MockListener::new();
+        mock_listener.expect_info().return_const(());
+        let mut config = Config::from_manifest(Path::new("Cargo.toml"), None, None, None, None, None, None, &mock_listener, "release").unwrap();
+        config.target = Some("i686-unknown-linux-gnu".into());
+        assert_eq!("i386-linux-gnu", config.multiarch_triple());
+        config.assets = Assets::with_resolved_assets(vec![
+            Asset::new(AssetSource::Path("target/release/libfoo.so".into()), "usr/lib/i386-linux-gnu/libfoo.so".into(), 0o644, IsBuilt::SamePackage),
+            Asset::new(AssetSource::Path("target/release/foo".into()), "usr/bin/foo".into(), 0o755, IsBuilt::SamePackage),
+            Asset::new(AssetSource::Path("target/release/foo.dat".into()), "usr/share/foo/foo.dat".into(), 0o644, IsBuilt::SamePackage),
+            Asset::new(AssetSource::Path("README.md".into()), "usr/share/doc/foo/README.md".into(), 0o644, IsBuilt::No),
+        ]);
+        assert!(config.multi_arch_conflicts().is_empty());
+        config.multi_arch = Some(MultiArch::Same);
+        assert_eq!(vec![Path::new("usr/bin/foo"), Path::new("usr/share/foo/foo.dat")], config.multi_arch_conflicts());
+    }
+
+    #[test]
+    fn third_party_licenses() {
+        let mut mock_listener = crate::listener::MockListener::new();
+        mock_listener.expect_info().return_const(());
+        let mut config = Config::from_manifest(Path::new("Cargo.toml"), None, None, None, None, None, None, &mock_listener, "release").unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let linked_crate = |name: &str, version: &str, notice: &str| {
+            let source_dir = dir.path().join(format!("{name}-{version}"));
+            fs::create_dir_all(&source_dir).unwrap();
+            fs::write(source_dir.join("LICENSE-MIT"), "MIT text").unwrap();
+            fs::write(source_dir.join("NOTICE"), notice).unwrap();
+            fs::write(source_dir.join("README.md"), "readme").unwrap();
+            LinkedCrate { name: name.into(), version: version.into(), license: Some("MIT".into()), license_file: None, authors: vec![], repository: None, source_dir }
+        };
+        config.linked_crates = vec![linked_crate("bar", "1.0.0", "bar"), linked_crate("foo", "1.0.0", "foo"), linked_crate("foo", "2.0.0", "foo")];
+        config.assets = Assets::with_resolved_assets(vec![]);
+        config.add_third_party_license_assets().unwrap();
+        assert!(config.assets.resolved.is_empty());
+
+        config.third_party_licenses = true;
+        config.add_third_party_license_assets().unwrap();
+        let assets: Vec<_> = config.assets.resolved.iter().map(|a| (a.c.target_path.to_str().unwrap(), match &a.source {
+            AssetSource::Data(data) => std::str::from_utf8(data).unwrap().to_owned(),
+            AssetSource::SymlinkTo(to) => format!("-> {}", to.display()),
+            _ => unreachable!(),
+        })).collect();
+        assert_eq!(assets, [
+            ("usr/share/doc/cargo-deb/third-party/bar/LICENSE-MIT", "MIT text".to_string()),
+            ("usr/share/doc/cargo-deb/third-party/bar/NOTICE", "bar".into()),
+            ("usr/share/doc/cargo-deb/third-party/foo-1.0.0/LICENSE-MIT", "-> ../bar/LICENSE-MIT".into()),
+            ("usr/share/doc/cargo-deb/third-party/foo-1.0.0/NOTICE", "foo".into()),
+            ("usr/share/doc/cargo-deb/third-party/foo-2.0.0/LICENSE-MIT", "-> ../bar/LICENSE-MIT".into()),
+            ("usr/share/doc/cargo-deb/third-party/foo-2.0.0/NOTICE", "-> ../foo-1.0.0/NOTICE".into()),
+        ]);
+    }
+
+    #[test]
+    fn statically_linked_crates() {
+        let metadata: CargoMetadata = serde_json::from_str(r#"{
+            "packages": [
+                {"id": "app", "name": "app", "version": "1.0.0", "source": null, "targets": [], "manifest_path": ""},
+                {"id": "serde", "name": "serde", "version": "1.0.1", "source": "registry", "targets": [{"name": "serde", "kind": ["lib"], "crate_types": ["lib"], "src_path": ""}], "manifest_path": ""},
+                {"id": "derive", "name": "serde_derive", "version": "1.0.1", "source": "registry", "targets": [{"name": "serde_derive", "kind": ["proc-macro"], "crate_types": ["proc-macro"], "src_path": ""}], "manifest_path": ""},
+                {"id": "syn", "name": "syn", "version": "2.0.0", "source": "registry", "targets": [], "manifest_path": ""},
+                {"id": "cc", "name": "cc", "version": "1.0.0", "source": "registry", "targets": [], "manifest_path": ""},
+                {"id": "local", "name": "local-lib", "version": "0.1.0", "source": null, "targets": [], "manifest_path": ""},
+                {"id": "pre", "name": "Pre_Release", "version": "0.2.0-beta.1+build", "source": "registry", "targets": [], "manifest_path": ""},
+                {"id": "testing", "name": "proptest", "version": "1.0.0", "source": "registry", "targets": [], "manifest_path": ""}
+            ],
+            "resolve": {"root": "app", "nodes": [
+                {"id": "app", "deps": [
+                    {"pkg": "serde", "dep_kinds": [{"kind": null}]},
+                    {"pkg": "derive", "dep_kinds": [{"kind": null}]},
+                    {"pkg": "cc", "dep_kinds": [{"kind": "build"}]},
+                    {"pkg": "local", "dep_kinds": [{"kind": null}]},
+                    {"pkg": "testing", "dep_kinds": [{"kind": "dev"}]}
+                ]},
+                {"id": "derive", "deps": [{"pkg": "syn", "dep_kinds": [{"kind": null}]}]},
+                {"id": "local", "deps": [{"pkg": "pre", "dep_kinds": [{"kind": null}]}]}
+            ]},
+            "target_directory": "target"
+        }"#).unwrap();
+        let crates = metadata.statically_linked_crates("app");
+        assert_eq!(vec![("Pre_Release", "0.2.0-beta.1+build"), ("serde", "1.0.1")], crates.iter().map(|c| (c.name.as_str(), c.version.as_str())).collect::<Vec<_>>());
+
+        let mut mock_listener = crate::listener::MockListener::new();
+        mock_listener.expect_info().return_const(());
+        let mut config = Config::from_manifest(Path::new("Cargo.toml"), None, None, None, None, None, None, &mock_listener, "release").unwrap();
+        config.linked_crates = crates;
+        assert_eq!(None, config.static_built_using());
+        config.static_built_using = true;
+        assert_eq!(Some("rust-pre-release (= 0.2.0~beta.1), rust-serde (= 1.0.1)"), config.static_built_using().as_deref());
+    }
+
+    #[test]
+    fn compression_setting() {
+        let deb: CargoDeb = toml::from_str(r#"compression = "zstd""#).unwrap();
+        let c = CompressConfig::from(deb.compression.unwrap());
+        assert_eq!(Format::Zst, c.format);
+        assert!(c.level.is_none());
+
+        let deb: CargoDeb = toml::from_str(r#"compression = { method = "xz", level = "9e", threads = 2 }"#).unwrap();
+        let c = CompressConfig::from(deb.compression.unwrap());
+        assert_eq!(Format::Xz, c.format);
+        assert_eq!(Some(Level { level: 9, extreme: true }), c.level);
+        assert_eq!(Some(2), c.threads);
+        assert!(!c.fast && !c.use_system);
+
+        let deb: CargoDeb = toml::from_str(r#"compression = { fast = true, system = true }"#).unwrap();
+        let c = CompressConfig::from(deb.compression.unwrap());
+        assert!(c.fast && c.use_system);
+
+        let deb: CargoDeb = toml::from_str(r#"compression = { method = "none" }"#).unwrap();
+        assert_eq!(Format::Uncompressed, CompressConfig::from(deb.compression.unwrap()).format);
+    }
+
+    #[test]
+    fn compress_debug_symbols_setting() {
+        let deb: CargoDeb = toml::from_str("compress-debug-symbols = true").unwrap();
+        assert_eq!(Some(DebugSectionsCompression::Zlib), deb.compress_debug_symbols.unwrap().method());
+        let deb: CargoDeb = toml::from_str("compress-debug-symbols = false").unwrap();
+        assert_eq!(None, deb.compress_debug_symbols.unwrap().method());
+        let deb: CargoDeb = toml::from_str(r#"compress-debug-symbols = "zstd""#).unwrap();
+        assert_eq!(Some(DebugSectionsCompression::Zstd), deb.compress_debug_symbols.unwrap().method());
+        assert!(toml::from_str::<CargoDeb>(r#"compress-debug-symbols = "xz""#).is_err());
+    }
+
     #[test]
     fn format_conffiles_empty() {
         let actual = format_conffiles::<String>(&[]);
@@ -1383,3 +2318,19 @@ fn deb_ver() {
     assert_eq!("1.2.0-new", manifest_version_string(&c, None));
     assert_eq!("1.2.0-new-11", manifest_version_string(&c, Some("11".into())));
 }
+
+#[test]
+fn parse_owner() {
+    let owner: Owner = "www-data:adm".parse().unwrap();
+    assert_eq!(("www-data", "adm"), (owner.user.as_str(), owner.group.as_str()));
+    assert!(owner.needs_chown());
+    let owner: Owner = "daemon".parse().unwrap();
+    assert_eq!("daemon:root", owner.to_string());
+    let owner: Owner = "1000:1000".parse().unwrap();
+    assert_eq!((Some(1000), Some(1000)), (owner.uid(), owner.gid()));
+    assert!(!owner.needs_chown());
+    assert!(!"root:root".parse::<Owner>().unwrap().needs_chown());
+    assert!("".parse::<Owner>().is_err());
+    assert!("foo:".parse::<Owner>().is_err());
+    assert!("a b".parse::<Owner>().is_err());
+}
@@ -0,0 +1,497 @@
+//! Package relationship fields, such as `Depends` and `Breaks`
+//! <https://www.debian.org/doc/debian-policy/ch-relationships.html>
+
+use crate::dependencies::compare_versions;
+use crate::error::*;
+use crate::manifest::debian_arch_tuple;
+use std::cmp::Ordering;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RelationField {
+    Depends,
+    PreDepends,
+    Recommends,
+    Suggests,
+    Enhances,
+    Conflicts,
+    Breaks,
+    Replaces,
+    Provides,
+}
+
+impl RelationField {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Depends => "Depends",
+            Self::PreDepends => "Pre-Depends",
+            Self::Recommends => "Recommends",
+            Self::Suggests => "Suggests",
+            Self::Enhances => "Enhances",
+            Self::Conflicts => "Conflicts",
+            Self::Breaks => "Breaks",
+            Self::Replaces => "Replaces",
+            Self::Provides => "Provides",
+        }
+    }
+
+    /// Fields that require other packages to be present, which can be satisfied by one of `|` alternatives,
+    /// and where two constraints on the same package can be merged into the stricter one.
+    fn is_dependency(self) -> bool {
+        matches!(self, Self::Depends | Self::PreDepends | Self::Recommends | Self::Suggests | Self::Enhances)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum VersionOp {
+    Less,
+    LessOrEqual,
+    Equal,
+    GreaterOrEqual,
+    Greater,
+}
+
+impl VersionOp {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Less => "<<",
+            Self::LessOrEqual => "<=",
+            Self::Equal => "=",
+            Self::GreaterOrEqual => ">=",
+            Self::Greater => ">>",
+        }
+    }
+}
+
+/// e.g. `(>= 1.2)`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct VersionConstraint {
+    pub op: VersionOp,
+    pub version: String,
+}
+
+impl VersionConstraint {
+    fn is_lower_bound(&self) -> bool {
+        matches!(self.op, VersionOp::GreaterOrEqual | VersionOp::Greater)
+    }
+
+    fn is_upper_bound(&self) -> bool {
+        matches!(self.op, VersionOp::LessOrEqual | VersionOp::Less)
+    }
+
+    /// Whether the `version` satisfies this constraint
+    fn allows(&self, version: &str) -> bool {
+        let ord = compare_versions(version, &self.version);
+        match self.op {
+            VersionOp::Less => ord == Ordering::Less,
+            VersionOp::LessOrEqual => ord != Ordering::Greater,
+            VersionOp::Equal => ord == Ordering::Equal,
+            VersionOp::GreaterOrEqual => ord != Ordering::Less,
+            VersionOp::Greater => ord == Ordering::Greater,
+        }
+    }
+
+    /// Combines two constraints into one that satisfies both, if that's possible
+    fn intersect(&self, other: &Self) -> Option<Self> {
+        let ord = compare_versions(&self.version, &other.version);
+        if self.is_lower_bound() && other.is_lower_bound() {
+            Some(match ord {
+                Ordering::Greater => self.clone(),
+                Ordering::Less => other.clone(),
+                Ordering::Equal => if self.op == VersionOp::Greater { self.clone() } else { other.clone() },
+            })
+        } else if self.is_upper_bound() && other.is_upper_bound() {
+            Some(match ord {
+                Ordering::Less => self.clone(),
+                Ordering::Greater => other.clone(),
+                Ordering::Equal => if self.op == VersionOp::Less { self.clone() } else { other.clone() },
+            })
+        } else if self.op == VersionOp::Equal && other.allows(&self.version) {
+            Some(self.clone())
+        } else if other.op == VersionOp::Equal && self.allows(&other.version) {
+            Some(other.clone())
+        } else {
+            None
+        }
+    }
+}
+
+/// e.g. `[amd64 arm64]` or `[!i386]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ArchRestriction {
+    pub negated: bool,
+    pub archs: Vec<String>,
+}
+
+impl ArchRestriction {
+    pub fn matches(&self, target_arch: &str) -> bool {
+        self.archs.iter().any(|spec| match_architecture(spec, target_arch)) != self.negated
+    }
+}
+
+/// Whether the architecture or wildcard in a restriction list, like `amd64`, `linux-any` or `any-arm`, includes the target's architecture.
+/// Both are compared as Debian arch tuples, so `linux-amd64` is the same as `amd64`.
+///
+/// <https://www.debian.org/doc/debian-policy/ch-customized-programs.html#s-arch-spec>
+fn match_architecture(spec: &str, target_arch: &str) -> bool {
+    if spec == target_arch || spec == "any" {
+        return true;
+    }
+    let target_tuple = match debian_arch_tuple(target_arch) {
+        Some(t) => t,
+        None => return false,
+    };
+    let parts: Vec<_> = spec.split('-').collect();
+    let spec_tuple = if parts.contains(&"any") {
+        if parts.len() > 4 {
+            return false;
+        }
+        // Wildcards with fewer parts are padded with `any` on the left, e.g. `linux-any` is `any-any-linux-any`
+        let mut tuple = ["any"; 4];
+        tuple[4 - parts.len()..].copy_from_slice(&parts);
+        tuple
+    } else {
+        match debian_arch_tuple(spec) {
+            Some(t) => t,
+            None => return false,
+        }
+    };
+    spec_tuple.iter().zip(target_tuple).all(|(&spec_part, target_part)| spec_part == "any" || spec_part == target_part)
+}
+
+/// A single package, e.g. `libfoo:any (>= 1.0) [amd64]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Relation {
+    pub name: String,
+    /// e.g. `any` in `python3:any`
+    pub arch_qualifier: Option<String>,
+    pub version: Option<VersionConstraint>,
+    pub arch_restriction: Option<ArchRestriction>,
+}
+
+/// One comma-separated item of a relationship field: packages separated by `|`, any of which satisfies it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Dependency {
+    pub alternatives: Vec<Relation>,
+}
+
+/// Contents of a relationship field
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct RelationList {
+    pub dependencies: Vec<Dependency>,
+}
+
+impl RelationList {
+    /// Parses a comma-separated list. Empty items are allowed, so that a trailing comma isn't an error.
+    pub fn parse(field: RelationField, input: &str) -> CDResult<Self> {
+        let dependencies = input.split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(|item| Dependency::parse(field, item))
+            .collect::<CDResult<_>>()?;
+        Ok(Self { dependencies })
+    }
+
+    pub fn extend(&mut self, other: Self) {
+        self.dependencies.extend(other.dependencies);
+    }
+
+    /// Removes alternatives that don't apply to the target architecture,
+    /// and removes the restrictions, since they're not allowed in binary packages.
+    pub fn retain_architecture(&mut self, target_arch: &str) {
+        for dep in &mut self.dependencies {
+            let mut alternatives = Vec::with_capacity(dep.alternatives.len());
+            for mut rel in dep.alternatives.drain(..) {
+                if let Some(restriction) = rel.arch_restriction.take() {
+                    if !restriction.matches(target_arch) {
+                        continue;
+                    }
+                }
+                alternatives.push(rel);
+            }
+            dep.alternatives = alternatives;
+        }
+        self.dependencies.retain(|dep| !dep.alternatives.is_empty());
+    }
+
+    /// Removes duplicates, and in dependency fields merges version constraints on the same package into the strictest one.
+    /// The order is kept, with merged items in the place of their first occurrence.
+    pub fn normalize(&mut self, field: RelationField) {
+        let mut merged: Vec<Dependency> = Vec::with_capacity(self.dependencies.len());
+        for dep in self.dependencies.drain(..) {
+            if field.is_dependency() {
+                if let Some(rel) = dep.single() {
+                    if merged.iter_mut().filter_map(Dependency::single_mut).any(|existing| existing.merge(rel)) {
+                        continue;
+                    }
+                }
+            }
+            if !merged.contains(&dep) {
+                merged.push(dep);
+            }
+        }
+        self.dependencies = merged;
+    }
+}
+
+impl fmt::Display for RelationList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, dep) in self.dependencies.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{dep}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Dependency {
+    pub fn parse(field: RelationField, input: &str) -> CDResult<Self> {
+        let invalid = |reason: &str| CargoDebError::InvalidRelation(field.name(), input.trim().to_owned(), reason.to_owned());
+        let alternatives = input.split('|')
+            .map(|alt| Relation::parse(alt.trim()).map_err(invalid))
+            .collect::<CDResult<Vec<_>>>()?;
+        if alternatives.len() > 1 && !field.is_dependency() {
+            return Err(invalid("alternatives with '|' are not allowed in this field"));
+        }
+        if field == RelationField::Provides && alternatives.iter().any(|rel| rel.version.as_ref().map_or(false, |v| v.op != VersionOp::Equal)) {
+            return Err(invalid("only exact versions with '=' can be provided"));
+        }
+        Ok(Self { alternatives })
+    }
+
+    fn single(&self) -> Option<&Relation> {
+        match &self.alternatives[..] {
+            [rel] => Some(rel),
+            _ => None,
+        }
+    }
+
+    fn single_mut(&mut self) -> Option<&mut Relation> {
+        match &mut self.alternatives[..] {
+            [rel] => Some(rel),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Dependency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, rel) in self.alternatives.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" | ")?;
+            }
+            write!(f, "{rel}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Relation {
+    fn parse(input: &str) -> Result<Self, &'static str> {
+        let name_end = input.find(|c: char| c.is_whitespace() || matches!(c, ':' | '(' | '[' | '<')).unwrap_or(input.len());
+        let (name, mut rest) = input.split_at(name_end);
+        if name.is_empty() {
+            return Err("missing package name");
+        }
+        if name.len() < 2 || !name.starts_with(|c: char| c.is_ascii_lowercase() || c.is_ascii_digit())
+            || !name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '+' | '-' | '.')) {
+            return Err("package names must be at least 2 characters long, and consist of lowercase letters, digits, '+', '-' and '.'");
+        }
+
+        let mut arch_qualifier = None;
+        if let Some(qualified) = rest.strip_prefix(':') {
+            let end = qualified.find(|c: char| !(c.is_ascii_alphanumeric() || c == '-')).unwrap_or(qualified.len());
+            if end == 0 {
+                return Err("missing architecture qualifier after ':'");
+            }
+            arch_qualifier = Some(qualified[..end].to_owned());
+            rest = &qualified[end..];
+        }
+
+        let mut version = None;
+        if let Some(constraint) = rest.trim_start().strip_prefix('(') {
+            let (constraint, after) = constraint.split_once(')').ok_or("missing ')' after the version")?;
+            let constraint = constraint.trim();
+            let op = [("<<", VersionOp::Less), ("<=", VersionOp::LessOrEqual), (">=", VersionOp::GreaterOrEqual), (">>", VersionOp::Greater), ("=", VersionOp::Equal)]
+                .into_iter()
+                .find(|(prefix, _)| constraint.starts_with(prefix));
+            let (prefix, op) = match op {
+                Some(op) => op,
+                None if constraint.starts_with(['<', '>']) => return Err("'<' and '>' are obsolete, use '<<', '<=', '>=' or '>>'"),
+                None => return Err("the version must start with one of '<<', '<=', '=', '>=', '>>'"),
+            };
+            let ver = constraint[prefix.len()..].trim();
+            if ver.is_empty() {
+                return Err("missing version after the operator");
+            }
+            if !ver.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '+' | '-' | '~' | ':')) {
+                return Err("versions can only contain letters, digits and '.', '+', '-', '~', ':'");
+            }
+            version = Some(VersionConstraint { op, version: ver.to_owned() });
+            rest = after;
+        }
+
+        let mut arch_restriction = None;
+        if let Some(archs) = rest.trim_start().strip_prefix('[') {
+            let (archs, after) = archs.split_once(']').ok_or("missing ']' after the architectures")?;
+            let archs: Vec<_> = archs.split_whitespace().collect();
+            if archs.is_empty() {
+                return Err("empty architecture list");
+            }
+            let negated = archs[0].starts_with('!');
+            if archs.iter().any(|arch| arch.starts_with('!') != negated) {
+                return Err("architecture list can't mix negated and non-negated architectures");
+            }
+            arch_restriction = Some(ArchRestriction {
+                negated,
+                archs: archs.iter().map(|arch| arch.trim_start_matches('!').to_owned()).collect(),
+            });
+            rest = after;
+        }
+
+        let rest = rest.trim();
+        if rest.starts_with('<') {
+            return Err("build profiles are only allowed in source packages");
+        }
+        if !rest.is_empty() {
+            return Err("unexpected text after the package name, version and architectures");
+        }
+        Ok(Self { name: name.to_owned(), arch_qualifier, version, arch_restriction })
+    }
+
+    /// Makes this relation stricter to also satisfy `other`, if they're about the same package
+    fn merge(&mut self, other: &Self) -> bool {
+        if self.name != other.name || self.arch_qualifier != other.arch_qualifier
+            || self.arch_restriction.is_some() || other.arch_restriction.is_some() {
+            return false;
+        }
+        match (&self.version, &other.version) {
+            (_, None) => true,
+            (None, Some(_)) => {
+                self.version = other.version.clone();
+                true
+            },
+            (Some(a), Some(b)) => match a.intersect(b) {
+                Some(stricter) => {
+                    self.version = Some(stricter);
+                    true
+                },
+                None => false,
+            },
+        }
+    }
+}
+
+impl fmt::Display for Relation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)?;
+        if let Some(q) = &self.arch_qualifier {
+            write!(f, ":{q}")?;
+        }
+        if let Some(v) = &self.version {
+            write!(f, " ({} {})", v.op.as_str(), v.version)?;
+        }
+        if let Some(r) = &self.arch_restriction {
+            f.write_str(" [")?;
+            for (i, arch) in r.archs.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(" ")?;
+                }
+                if r.negated {
+                    f.write_str("!")?;
+                }
+                f.write_str(arch)?;
+            }
+            f.write_str("]")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> RelationList {
+        RelationList::parse(RelationField::Depends, input).unwrap()
+    }
+
+    #[test]
+    fn parse_relations() {
+        let list = parse("libc6 (>= 2.28), python3:any | python3-minimal (<<4),  libjpeg64-turbo [!amd64 !i386],");
+        assert_eq!(3, list.dependencies.len());
+        let rel = &list.dependencies[1].alternatives[0];
+        assert_eq!("python3", rel.name);
+        assert_eq!(Some("any"), rel.arch_qualifier.as_deref());
+        assert_eq!(Some(VersionConstraint { op: VersionOp::Less, version: "4".into() }), list.dependencies[1].alternatives[1].version);
+        assert_eq!(Some(ArchRestriction { negated: true, archs: vec!["amd64".into(), "i386".into()] }), list.dependencies[2].alternatives[0].arch_restriction);
+        assert_eq!("libc6 (>= 2.28), python3:any | python3-minimal (<< 4), libjpeg64-turbo [!amd64 !i386]", list.to_string());
+    }
+
+    #[test]
+    fn invalid_relations() {
+        for bad in ["Libc6", "libc6 (> 2)", "libc6 (>= )", "libc6 (>= 2", "foo [amd64 !i386]", "foo <!nocheck>", "foo bar", "x", "foo (= 1 2)"] {
+            let err = RelationList::parse(RelationField::Depends, bad).unwrap_err();
+            assert!(matches!(err, CargoDebError::InvalidRelation("Depends", ..)), "{bad}: {err}");
+        }
+        assert!(RelationList::parse(RelationField::Conflicts, "foo | bar").is_err());
+        assert!(RelationList::parse(RelationField::Provides, "foo (>= 1)").is_err());
+        assert!(RelationList::parse(RelationField::Provides, "foo (= 1)").is_ok());
+    }
+
+    #[test]
+    fn merge_strictest() {
+        let mut list = parse("libc6 (>= 2.28), zlib1g, libc6, libfoo (<< 3), libc6 (>= 2.31), libfoo (<= 2), libbar (= 1.5), libbar (>= 1), zlib1g, foo | bar, foo | bar");
+        list.normalize(RelationField::Depends);
+        assert_eq!("libc6 (>= 2.31), zlib1g, libfoo (<= 2), libbar (= 1.5), foo | bar", list.to_string());
+
+        let mut list = parse("foo (>= 2), foo (<< 3), foo (>> 2)");
+        list.normalize(RelationField::Depends);
+        assert_eq!("foo (>> 2), foo (<< 3)", list.to_string());
+
+        let mut list = RelationList::parse(RelationField::Breaks, "foo (<< 2), foo (<< 3), foo (<< 2)").unwrap();
+        list.normalize(RelationField::Breaks);
+        assert_eq!("foo (<< 2), foo (<< 3)", list.to_string());
+    }
+
+    #[test]
+    fn retain_architecture() {
+        let mut list = parse("libjpeg64-turbo [armhf], libfoo [!amd64 !i386] | libbar, libbaz [linux-any], libqux [arm64 any-amd64]");
+        list.retain_architecture("amd64");
+        assert_eq!("libbar, libbaz, libqux", list.to_string());
+    }
+
+    #[test]
+    fn arch_wildcards() {
+        for (spec, arch, matches) in [
+            ("amd64", "amd64", true),
+            ("amd64", "arm64", false),
+            ("any", "riscv64", true),
+            ("all", "amd64", false),
+            ("linux-any", "amd64", true),
+            ("linux-any", "hurd-i386", false),
+            ("hurd-any", "hurd-i386", true),
+            ("any-amd64", "amd64", true),
+            ("any-amd64", "x32", true),
+            ("any-amd64", "i386", false),
+            ("any-arm", "armhf", true),
+            ("any-arm", "armel", true),
+            ("any-arm", "arm64", false),
+            ("gnu-linux-any", "ppc64el", true),
+            ("musl-linux-any", "ppc64el", false),
+            ("musl-linux-any", "musl-linux-amd64", true),
+            ("eabihf-any-any-any", "armhf", true),
+            ("eabihf-any-any-any", "armel", false),
+            ("any-any-any-any-any", "amd64", false),
+            ("linux-amd64", "amd64", true),
+            ("amd64", "linux-amd64", true),
+            ("linux-armhf", "armel", false),
+            ("eabi-any-any-any", "armel", true),
+            ("musl-linux-amd64", "amd64", false),
+        ] {
+            assert_eq!(matches, match_architecture(spec, arch), "{spec} {arch}");
+        }
+    }
+}
@@ -0,0 +1,257 @@
+//! Software bills of materials of the package's files and the crates linked into it
+//!
+//! <https://spdx.github.io/spdx-spec/v2.3/>
+//! <https://cyclonedx.org/docs/1.5/json/>
+
+use crate::data::FileHashes;
+use crate::error::*;
+use crate::manifest::{Config, SbomFormat};
+use crate::spdx::LicenseExpr;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+impl SbomFormat {
+    /// Appended to the name of the `.deb` file, without its extension
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Spdx => "spdx.json",
+            Self::CycloneDx => "cdx.json",
+        }
+    }
+}
+
+/// Where the SBOM is installed with `install-sbom`
+pub(crate) fn installed_path(options: &Config, format: SbomFormat) -> PathBuf {
+    Path::new("usr/share/doc").join(&options.deb_name).join(format!("{}.{}", options.deb_name, format.extension()))
+}
+
+/// Describes the package's files with their hashes, and crates it's statically linked with.
+/// `time` is used as the creation date, so that the output is reproducible with `SOURCE_DATE_EPOCH`.
+pub fn generate(format: SbomFormat, options: &Config, hashes: &HashMap<PathBuf, FileHashes>, time: u64) -> CDResult<Vec<u8>> {
+    // Only the assets, so that an installed SBOM doesn't describe itself
+    let files: Vec<_> = options.assets.resolved.iter()
+        .filter_map(|asset| Some((&asset.c.target_path, hashes.get(&asset.c.target_path)?)))
+        .collect();
+
+    // A unique but reproducible ID of the document
+    let mut id = md5::Context::new();
+    id.consume(format!("{}_{}_{}\n", options.deb_name, options.deb_version, options.architecture));
+    for (path, hash) in &files {
+        id.consume(format!("{:x}  {}\n", hash.md5, path.display()));
+    }
+    let id = format!("{:x}", id.compute());
+
+    let purl = format!("pkg:deb/{}@{}?arch={}", options.deb_name, options.deb_version, options.architecture);
+    let doc = match format {
+        SbomFormat::Spdx => spdx(options, &files, &id, &purl, time),
+        SbomFormat::CycloneDx => cyclonedx(options, &files, &id, &purl, time),
+    };
+    let mut out = serde_json::to_vec_pretty(&doc)?;
+    out.push(b'\n');
+    Ok(out)
+}
+
+fn spdx(options: &Config, files: &[(&PathBuf, &FileHashes)], id: &str, purl: &str, time: u64) -> Value {
+    let mut packages = vec![json!({
+        "SPDXID": "SPDXRef-Package",
+        "name": options.deb_name,
+        "versionInfo": options.deb_version,
+        "supplier": format!("Person: {}", options.maintainer),
+        "downloadLocation": options.repository.as_deref().unwrap_or("NOASSERTION"),
+        "homepage": options.homepage.as_deref().unwrap_or("NOASSERTION"),
+        "filesAnalyzed": false,
+        "licenseConcluded": "NOASSERTION",
+        "licenseDeclared": options.license.as_deref().map_or_else(|| "NOASSERTION".into(), spdx_expression),
+        "copyrightText": options.copyright,
+        "summary": options.description,
+        "primaryPackagePurpose": "INSTALL",
+        "externalRefs": [{"referenceCategory": "PACKAGE-MANAGER", "referenceType": "purl", "referenceLocator": purl}],
+    })];
+    let mut relationships = vec![json!({"spdxElementId": "SPDXRef-DOCUMENT", "relationshipType": "DESCRIBES", "relatedSpdxElement": "SPDXRef-Package"})];
+
+    for (n, c) in options.linked_crates.iter().enumerate() {
+        let spdx_id = format!("SPDXRef-Crate-{n}");
+        packages.push(json!({
+            "SPDXID": spdx_id,
+            "name": c.name,
+            "versionInfo": c.version,
+            "downloadLocation": c.repository.as_deref().unwrap_or("NOASSERTION"),
+            "filesAnalyzed": false,
+            "licenseConcluded": "NOASSERTION",
+            "licenseDeclared": c.license.as_deref().map_or_else(|| "NOASSERTION".into(), spdx_expression),
+            "copyrightText": "NOASSERTION",
+            "primaryPackagePurpose": "LIBRARY",
+            "externalRefs": [{"referenceCategory": "PACKAGE-MANAGER", "referenceType": "purl", "referenceLocator": format!("pkg:cargo/{}@{}", c.name, c.version)}],
+        }));
+        relationships.push(json!({"spdxElementId": "SPDXRef-Package", "relationshipType": "STATIC_LINK", "relatedSpdxElement": spdx_id}));
+    }
+
+    let files: Vec<_> = files.iter().enumerate().map(|(n, (path, hash))| {
+        let spdx_id = format!("SPDXRef-File-{n}");
+        relationships.push(json!({"spdxElementId": "SPDXRef-Package", "relationshipType": "CONTAINS", "relatedSpdxElement": spdx_id}));
+        json!({
+            "SPDXID": spdx_id,
+            "fileName": format!("./{}", path.display()),
+            "checksums": checksums(hash, "algorithm", "checksumValue", ["SHA1", "SHA256", "MD5"]),
+            "licenseConcluded": "NOASSERTION",
+            "copyrightText": "NOASSERTION",
+        })
+    }).collect();
+
+    json!({
+        "spdxVersion": "SPDX-2.3",
+        "dataLicense": "CC0-1.0",
+        "SPDXID": "SPDXRef-DOCUMENT",
+        "name": format!("{}_{}_{}", options.deb_name, options.deb_version, options.architecture),
+        "documentNamespace": format!("https://spdx.org/spdxdocs/{}-{}-{id}", options.deb_name, options.deb_version),
+        "creationInfo": {
+            "created": iso8601(time),
+            "creators": [format!("Tool: cargo-deb-{}", env!("CARGO_PKG_VERSION"))],
+        },
+        "packages": packages,
+        "files": files,
+        "relationships": relationships,
+    })
+}
+
+fn cyclonedx(options: &Config, files: &[(&PathBuf, &FileHashes)], id: &str, purl: &str, time: u64) -> Value {
+    let licenses = |license: Option<&str>| license.map_or_else(|| json!([]), |l| json!([{"expression": spdx_expression(l)}]));
+
+    let mut components = Vec::with_capacity(options.linked_crates.len() + files.len());
+    let mut crate_refs = Vec::with_capacity(options.linked_crates.len());
+    for c in &options.linked_crates {
+        let crate_purl = format!("pkg:cargo/{}@{}", c.name, c.version);
+        components.push(json!({
+            "type": "library",
+            "bom-ref": crate_purl,
+            "name": c.name,
+            "version": c.version,
+            "purl": crate_purl,
+            "licenses": licenses(c.license.as_deref()),
+        }));
+        crate_refs.push(crate_purl);
+    }
+    for (path, hash) in files {
+        components.push(json!({
+            "type": "file",
+            "bom-ref": format!("file:/{}", path.display()),
+            "name": format!("/{}", path.display()),
+            "hashes": checksums(hash, "alg", "content", ["SHA-1", "SHA-256", "MD5"]),
+        }));
+    }
+
+    // MD5 formatted as a name-based UUID
+    let uuid = format!("{}-{}-3{}-{:x}{}-{}", &id[..8], &id[8..12], &id[13..16], (u8::from_str_radix(&id[16..17], 16).unwrap_or(0) & 0x3) | 0x8, &id[17..20], &id[20..32]);
+    json!({
+        "bomFormat": "CycloneDX",
+        "specVersion": "1.5",
+        "serialNumber": format!("urn:uuid:{uuid}"),
+        "version": 1,
+        "metadata": {
+            "timestamp": iso8601(time),
+            "tools": {"components": [{"type": "application", "name": "cargo-deb", "version": env!("CARGO_PKG_VERSION")}]},
+            "component": {
+                "type": "application",
+                "bom-ref": purl,
+                "name": options.deb_name,
+                "version": options.deb_version,
+                "description": options.description,
+                "copyright": options.copyright,
+                "purl": purl,
+                "licenses": licenses(options.license.as_deref()),
+            },
+        },
+        "components": components,
+        "dependencies": [{"ref": purl, "dependsOn": crate_refs}],
+    })
+}
+
+/// SHA-1, SHA-256 and MD5 of a file, with the given field and algorithm names
+fn checksums(hash: &FileHashes, alg_key: &str, value_key: &str, [sha1_alg, sha256_alg, md5_alg]: [&str; 3]) -> Value {
+    let mut out = Vec::with_capacity(3);
+    if let Some((sha1, sha256)) = &hash.sha {
+        out.push(json!({alg_key: sha1_alg, value_key: sha1}));
+        out.push(json!({alg_key: sha256_alg, value_key: sha256}));
+    }
+    out.push(json!({alg_key: md5_alg, value_key: format!("{:x}", hash.md5)}));
+    Value::Array(out)
+}
+
+/// Normalized, e.g. old crates use `/` instead of `OR`
+fn spdx_expression(license: &str) -> String {
+    LicenseExpr::parse(license).map_or_else(|_| license.to_owned(), |e| e.to_string())
+}
+
+/// UTC date and time, e.g. `2022-10-17T12:34:56Z`
+fn iso8601(unix_time: u64) -> String {
+    let (days, secs) = (unix_time / 86400, unix_time % 86400);
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+#[test]
+fn timestamps() {
+    assert_eq!("1970-01-01T00:00:00Z", iso8601(0));
+    assert_eq!("2000-02-29T23:59:59Z", iso8601(951_868_799));
+    assert_eq!("2022-10-17T12:34:56Z", iso8601(1_666_010_096));
+}
+
+#[test]
+fn sbom_documents() {
+    let mut mock_listener = crate::listener::MockListener::new();
+    mock_listener.expect_info().return_const(());
+    let mut config = Config::from_manifest(Path::new("Cargo.toml"), None, None, None, None, None, None, &mock_listener, "release").unwrap();
+    config.assets = crate::manifest::Assets::with_resolved_assets(vec![
+        crate::manifest::Asset::new(crate::manifest::AssetSource::Data(b"bin".to_vec()), "usr/bin/foo".into(), 0o755, crate::manifest::IsBuilt::No),
+        crate::manifest::Asset::new(crate::manifest::AssetSource::SymlinkTo("foo".into()), "usr/bin/bar".into(), 0o777, crate::manifest::IsBuilt::No),
+    ]);
+    let file_hashes = |data: &[u8]| FileHashes {
+        md5: md5::compute(data),
+        sha: Some((format!("{:x}", <sha1::Sha1 as sha2::Digest>::digest(data)), format!("{:x}", <sha2::Sha256 as sha2::Digest>::digest(data)))),
+    };
+    let hashes: HashMap<_, _> = [(PathBuf::from("usr/bin/foo"), file_hashes(b"bin")), (installed_path(&config, SbomFormat::Spdx), file_hashes(b"x"))].into_iter().collect();
+
+    let spdx: Value = serde_json::from_slice(&generate(SbomFormat::Spdx, &config, &hashes, 0).unwrap()).unwrap();
+    assert_eq!("SPDX-2.3", spdx["spdxVersion"]);
+    assert_eq!("1970-01-01T00:00:00Z", spdx["creationInfo"]["created"]);
+    assert_eq!(json!([{
+        "SPDXID": "SPDXRef-File-0",
+        "fileName": "./usr/bin/foo",
+        "checksums": [
+            {"algorithm": "SHA1", "checksumValue": "9a5d56716d566997fa290054d161af96305aff9a"},
+            {"algorithm": "SHA256", "checksumValue": "51a1f05af85e342e3c849b47d387086476282d5f50dc240c19216d6edfb1eb5a"},
+            {"algorithm": "MD5", "checksumValue": format!("{:x}", md5::compute(b"bin"))},
+        ],
+        "licenseConcluded": "NOASSERTION",
+        "copyrightText": "NOASSERTION",
+    }]), spdx["files"]);
+    assert_eq!(config.linked_crates.len() + 1, spdx["packages"].as_array().unwrap().len());
+    assert_eq!("MIT", spdx["packages"][0]["licenseDeclared"]);
+
+    let cdx: Value = serde_json::from_slice(&generate(SbomFormat::CycloneDx, &config, &hashes, 0).unwrap()).unwrap();
+    assert_eq!("CycloneDX", cdx["bomFormat"]);
+    assert_eq!("cargo-deb", cdx["metadata"]["component"]["name"]);
+    let file = cdx["components"].as_array().unwrap().last().unwrap();
+    assert_eq!("/usr/bin/foo", file["name"]);
+    assert_eq!("SHA-256", file["hashes"][1]["alg"]);
+    assert_eq!(spdx["files"][0]["checksums"][1]["checksumValue"], file["hashes"][1]["content"]);
+    let serial = cdx["serialNumber"].as_str().unwrap();
+    assert_eq!(45, serial.len());
+    assert_eq!(serial, serde_json::from_slice::<Value>(&generate(SbomFormat::CycloneDx, &config, &hashes, 1).unwrap()).unwrap()["serialNumber"]);
+}
+
+#[test]
+fn spdx_expressions() {
+    assert_eq!("MIT OR Apache-2.0", spdx_expression("MIT/Apache-2.0"));
+    assert_eq!("MIT AND Zlib", spdx_expression("MIT and Zlib"));
+}
@@ -0,0 +1,309 @@
+//! SPDX license expressions from Cargo's `license` field, and their Debian `copyright` equivalents
+//!
+//! <https://spdx.github.io/spdx-spec/v2.3/SPDX-license-expressions/>
+//! <https://www.debian.org/doc/packaging-manuals/copyright-format/1.0/#license-specification>
+
+use std::fmt;
+
+/// Licenses commonly used by crates. Others are allowed, but get a warning, since they're likely typos.
+const KNOWN_LICENSES: &[&str] = &[
+    "0BSD", "AFL-3.0", "AGPL-3.0", "AGPL-3.0-only", "AGPL-3.0-or-later", "Apache-1.1", "Apache-2.0",
+    "Artistic-1.0", "Artistic-1.0-Perl", "Artistic-2.0", "BlueOak-1.0.0", "BSD-1-Clause", "BSD-2-Clause",
+    "BSD-2-Clause-Patent", "BSD-3-Clause", "BSD-3-Clause-Clear", "BSD-4-Clause", "BSL-1.0", "bzip2-1.0.6",
+    "CC-BY-3.0", "CC-BY-4.0", "CC-BY-SA-3.0", "CC-BY-SA-4.0", "CC0-1.0", "CDDL-1.0", "CDLA-Permissive-2.0",
+    "ECL-2.0", "EPL-1.0", "EPL-2.0", "EUPL-1.1", "EUPL-1.2", "FSFAP", "GFDL-1.2", "GFDL-1.2-only",
+    "GFDL-1.2-or-later", "GFDL-1.3", "GFDL-1.3-only", "GFDL-1.3-or-later", "GPL-1.0", "GPL-1.0-only",
+    "GPL-1.0-or-later", "GPL-2.0", "GPL-2.0-only", "GPL-2.0-or-later", "GPL-3.0", "GPL-3.0-only",
+    "GPL-3.0-or-later", "HPND", "ICU", "IJG", "ISC", "LGPL-2.0", "LGPL-2.0-only", "LGPL-2.0-or-later",
+    "LGPL-2.1", "LGPL-2.1-only", "LGPL-2.1-or-later", "LGPL-3.0", "LGPL-3.0-only", "LGPL-3.0-or-later",
+    "libpng-2.0", "MIT", "MIT-0", "MIT-CMU", "MPL-1.1", "MPL-2.0", "MPL-2.0-no-copyleft-exception",
+    "MS-PL", "MulanPSL-2.0", "NCSA", "OFL-1.1", "OpenSSL", "OSL-3.0", "PostgreSQL", "PSF-2.0",
+    "Python-2.0", "Ruby", "Unicode-3.0", "Unicode-DFS-2016", "Unlicense", "UPL-1.0", "W3C", "WTFPL",
+    "X11", "Zlib", "zlib-acknowledgement", "ZPL-2.1",
+];
+
+const KNOWN_EXCEPTIONS: &[&str] = &[
+    "Autoconf-exception-3.0", "Bison-exception-2.2", "Classpath-exception-2.0", "GCC-exception-3.1",
+    "LGPL-3.0-linking-exception", "LLVM-exception", "OpenSSL-exception", "Qt-LGPL-exception-1.1",
+];
+
+/// Licenses shipped in `/usr/share/common-licenses` by `base-files`, by their Debian names
+const COMMON_LICENSES: &[&str] = &[
+    "Apache-2.0", "Artistic", "CC0-1.0", "GFDL-1.2", "GFDL-1.3", "GPL-1", "GPL-2", "GPL-3",
+    "LGPL-2", "LGPL-2.1", "LGPL-3", "MPL-1.1", "MPL-2.0",
+];
+
+/// Parsed SPDX license expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum LicenseExpr {
+    /// License identifier. `+` means "or later".
+    License { id: String, or_later: bool },
+    With(Box<LicenseExpr>, String),
+    And(Vec<LicenseExpr>),
+    Or(Vec<LicenseExpr>),
+}
+
+impl LicenseExpr {
+    /// Besides the standard syntax, accepts lowercase operators and `/` instead of `OR`, which old crates use
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        let mut tokens = Vec::new();
+        for word in expr.split_whitespace() {
+            let mut rest = word;
+            while !rest.is_empty() {
+                let len = rest.find(['(', ')', '/']).map_or(rest.len(), |pos| pos.max(1));
+                tokens.push(&rest[..len]);
+                rest = &rest[len..];
+            }
+        }
+        let mut parser = Parser { tokens: &tokens, pos: 0 };
+        let parsed = parser.or()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(parsed),
+            Some(unexpected) => Err(format!("unexpected '{unexpected}'")),
+        }
+    }
+
+    /// Identifiers of licenses and exceptions that aren't on the list of commonly used ones
+    pub fn unknown_ids(&self) -> Vec<&str> {
+        match self {
+            Self::License { id, .. } => {
+                if KNOWN_LICENSES.contains(&id.as_str()) || id.starts_with("LicenseRef-") { vec![] } else { vec![id.as_str()] }
+            },
+            Self::With(license, exception) => {
+                let mut ids = license.unknown_ids();
+                if !KNOWN_EXCEPTIONS.contains(&exception.as_str()) {
+                    ids.push(exception);
+                }
+                ids
+            },
+            Self::And(all) | Self::Or(all) => all.iter().flat_map(|e| e.unknown_ids()).collect(),
+        }
+    }
+
+    /// Debian short names of all licenses in the expression, without exceptions and duplicates
+    pub fn debian_names(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        self.collect_debian_names(&mut names);
+        names
+    }
+
+    fn collect_debian_names(&self, names: &mut Vec<String>) {
+        match self {
+            Self::License { id, or_later } => {
+                let name = debian_name(id, *or_later);
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            },
+            Self::With(license, _) => license.collect_debian_names(names),
+            Self::And(all) | Self::Or(all) => all.iter().for_each(|e| e.collect_debian_names(names)),
+        }
+    }
+
+    /// The `License` field syntax of Debian's `copyright` files, e.g. `GPL-2+ or Apache-2.0 with LLVM exception`.
+    ///
+    /// In Debian's syntax `and` takes precedence over `or`, and there are no parenthesis. Only a comma can lower the precedence,
+    /// so `(A or B) and C` is written as `A or B, and C`. Returns `None` for expressions that can't be written that way.
+    pub fn to_debian(&self) -> Option<String> {
+        self.debian_syntax(true)
+    }
+
+    fn debian_syntax(&self, top_level: bool) -> Option<String> {
+        match self {
+            Self::License { id, or_later } => Some(debian_name(id, *or_later)),
+            Self::With(license, exception) => {
+                Some(format!("{} with {} exception", license.debian_syntax(false)?, exception.replacen("-exception", "", 1)))
+            },
+            Self::Or(all) => Some(all.iter().map(|e| e.debian_syntax(false)).collect::<Option<Vec<_>>>()?.join(" or ")),
+            Self::And(all) => {
+                let mut or_groups = Vec::new();
+                let mut others = Vec::new();
+                Self::flatten_and(all, &mut or_groups, &mut others);
+                let others = others.iter().map(|e| e.debian_syntax(false)).collect::<Option<Vec<_>>>()?.join(" and ");
+                match or_groups.as_slice() {
+                    [] => Some(others),
+                    // the comma applies to everything before it, so there can be only one group, and not in a nested expression
+                    [or_group] if top_level => Some(format!("{}, and {others}", or_group.debian_syntax(false)?)),
+                    _ => None,
+                }
+            },
+        }
+    }
+
+    fn flatten_and<'a>(all: &'a [Self], or_groups: &mut Vec<&'a Self>, others: &mut Vec<&'a Self>) {
+        for e in all {
+            match e {
+                Self::And(nested) => Self::flatten_and(nested, or_groups, others),
+                Self::Or(_) => or_groups.push(e),
+                _ => others.push(e),
+            }
+        }
+    }
+}
+
+impl fmt::Display for LicenseExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // `AND` takes precedence over `OR`
+        let join = |all: &[Self], op: &str, needs_parens: fn(&Self) -> bool| {
+            all.iter().map(|e| if needs_parens(e) { format!("({e})") } else { e.to_string() }).collect::<Vec<_>>().join(op)
+        };
+        match self {
+            Self::License { id, or_later } => write!(f, "{id}{}", if *or_later { "+" } else { "" }),
+            Self::With(license, exception) => write!(f, "{license} WITH {exception}"),
+            Self::And(all) => f.write_str(&join(all, " AND ", |e| matches!(e, Self::And(_) | Self::Or(_)))),
+            Self::Or(all) => f.write_str(&join(all, " OR ", |e| matches!(e, Self::Or(_)))),
+        }
+    }
+}
+
+struct Parser<'a> {
+    tokens: &'a [&'a str],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn next_if(&mut self, matches: impl Fn(&str) -> bool) -> Option<&str> {
+        let token = *self.tokens.get(self.pos)?;
+        if !matches(token) {
+            return None;
+        }
+        self.pos += 1;
+        Some(token)
+    }
+
+    fn or(&mut self) -> Result<LicenseExpr, String> {
+        let mut all = vec![self.and()?];
+        while self.next_if(|t| t == "OR" || t == "or" || t == "/").is_some() {
+            all.push(self.and()?);
+        }
+        Ok(if all.len() == 1 { all.remove(0) } else { LicenseExpr::Or(all) })
+    }
+
+    fn and(&mut self) -> Result<LicenseExpr, String> {
+        let mut all = vec![self.with()?];
+        while self.next_if(|t| t == "AND" || t == "and").is_some() {
+            all.push(self.with()?);
+        }
+        Ok(if all.len() == 1 { all.remove(0) } else { LicenseExpr::And(all) })
+    }
+
+    fn with(&mut self) -> Result<LicenseExpr, String> {
+        let license = self.primary()?;
+        if self.next_if(|t| t == "WITH" || t == "with").is_none() {
+            return Ok(license);
+        }
+        if !matches!(license, LicenseExpr::License { .. }) {
+            return Err("WITH can only follow a license identifier".into());
+        }
+        match self.next_if(is_identifier) {
+            Some(exception) => Ok(LicenseExpr::With(Box::new(license), exception.to_owned())),
+            None => Err("expected an exception identifier after WITH".into()),
+        }
+    }
+
+    fn primary(&mut self) -> Result<LicenseExpr, String> {
+        if self.next_if(|t| t == "(").is_some() {
+            let inner = self.or()?;
+            if self.next_if(|t| t == ")").is_none() {
+                return Err("missing ')'".into());
+            }
+            return Ok(inner);
+        }
+        match self.tokens.get(self.pos) {
+            Some(token) if is_identifier(token.trim_end_matches('+')) && !is_operator(token) => {
+                self.pos += 1;
+                let or_later = token.ends_with('+');
+                Ok(LicenseExpr::License { id: token.trim_end_matches('+').to_owned(), or_later })
+            },
+            Some(token) => Err(format!("expected a license identifier, found '{token}'")),
+            None => Err("expected a license identifier".into()),
+        }
+    }
+}
+
+fn is_operator(token: &str) -> bool {
+    matches!(token, "AND" | "OR" | "WITH" | "and" | "or" | "with")
+}
+
+fn is_identifier(token: &str) -> bool {
+    !token.is_empty() && !is_operator(token) && token.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+}
+
+/// DEP-5 uses `GPL-2+` instead of `GPL-2.0-or-later`, etc.
+fn debian_name(id: &str, or_later: bool) -> String {
+    let gnu = ["AGPL-", "GPL-", "LGPL-", "GFDL-"].iter().any(|prefix| id.starts_with(prefix));
+    let mut or_later = or_later;
+    let name = if gnu {
+        let version = if let Some(v) = id.strip_suffix("-or-later") {
+            or_later = true;
+            v
+        } else {
+            id.trim_end_matches("-only")
+        };
+        version.trim_end_matches(".0").to_owned()
+    } else {
+        match id {
+            "Artistic-1.0-Perl" => "Artistic".into(),
+            "BSD-2-Clause" => "BSD-2-clause".into(),
+            "BSD-3-Clause" => "BSD-3-clause".into(),
+            "BSD-4-Clause" => "BSD-4-clause".into(),
+            other => other.to_owned(),
+        }
+    };
+    if or_later { format!("{name}+") } else { name }
+}
+
+/// Name of the file in `/usr/share/common-licenses` with the text of the license, if there is one
+fn common_license(debian_name: &str) -> Option<&'static str> {
+    let name = debian_name.trim_end_matches('+');
+    COMMON_LICENSES.iter().copied().find(|&l| l == name)
+}
+
+/// Standalone `License` paragraph body pointing to `/usr/share/common-licenses`
+pub(crate) fn common_license_reference(debian_name: &str) -> Option<String> {
+    common_license(debian_name).map(|file| {
+        format!(" On Debian systems, the complete text of the {debian_name} license can be found in\n /usr/share/common-licenses/{file}.\n")
+    })
+}
+
+/// The license in Debian's syntax, or as-is if it's not a valid SPDX expression or can't be translated
+pub(crate) fn debian_license(license: &str) -> String {
+    LicenseExpr::parse(license).ok().and_then(|e| e.to_debian()).unwrap_or_else(|| license.to_owned())
+}
+
+#[test]
+fn parse_expressions() {
+    let parsed = LicenseExpr::parse("MIT OR (Apache-2.0 WITH LLVM-exception AND GPL-2.0+)").unwrap();
+    assert_eq!("MIT OR Apache-2.0 WITH LLVM-exception AND GPL-2.0+", parsed.to_string());
+    assert_eq!("MIT or Apache-2.0 with LLVM exception and GPL-2+", parsed.to_debian().unwrap());
+    assert_eq!(vec!["MIT", "Apache-2.0", "GPL-2+"], parsed.debian_names());
+
+    let parsed = LicenseExpr::parse("MIT/Apache-2.0").unwrap();
+    assert_eq!("MIT OR Apache-2.0", parsed.to_string());
+    let parsed = LicenseExpr::parse("(MIT or LGPL-2.1-or-later) and BSD-3-Clause").unwrap();
+    assert_eq!("(MIT OR LGPL-2.1-or-later) AND BSD-3-Clause", parsed.to_string());
+    assert_eq!("MIT or LGPL-2.1+, and BSD-3-clause", parsed.to_debian().unwrap());
+    assert_eq!("MIT or Zlib, and BSD-3-clause and ISC", debian_license("BSD-3-Clause AND (MIT OR Zlib) AND ISC"));
+    // (MIT or Zlib) and (Apache-2.0 or BSL-1.0) can't be written with one comma
+    assert_eq!("(MIT OR Zlib) AND (Apache-2.0 OR BSL-1.0)", debian_license("(MIT OR Zlib) AND (Apache-2.0 OR BSL-1.0)"));
+    assert_eq!("ISC OR (BSD-3-Clause AND (MIT OR Zlib))", debian_license("ISC OR (BSD-3-Clause AND (MIT OR Zlib))"));
+    assert_eq!("GPL-3 or AGPL-3+ or GFDL-1.3", debian_license("GPL-3.0-only OR AGPL-3.0-or-later OR GFDL-1.3-only"));
+
+    for invalid in ["", "MIT OR", "(MIT", "MIT)", "MIT Apache-2.0", "MIT WITH", "(MIT OR Zlib) WITH LLVM-exception", "GPL-2.0, MIT", "OR MIT"] {
+        assert!(LicenseExpr::parse(invalid).is_err(), "{invalid}");
+    }
+    assert_eq!("GPL-2.0, MIT", debian_license("GPL-2.0, MIT"));
+}
+
+#[test]
+fn unknown_ids() {
+    assert!(LicenseExpr::parse("MIT OR LicenseRef-Proprietary").unwrap().unknown_ids().is_empty());
+    assert_eq!(vec!["Apache2", "LLVM"], LicenseExpr::parse("MIT OR Apache2 WITH LLVM").unwrap().unknown_ids());
+}
+
+#[test]
+fn common_licenses() {
+    assert_eq!(Some("GPL-2"), common_license("GPL-2+"));
+    assert_eq!(None, common_license("MIT"));
+    assert!(common_license_reference("Apache-2.0").unwrap().ends_with("/usr/share/common-licenses/Apache-2.0.\n"));
+}
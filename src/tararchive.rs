@@ -1,5 +1,6 @@
-use std::io::Write;
+use std::io::{Read, Write};
 use crate::error::CDResult;
+use crate::manifest::Owner;
 use std::collections::HashSet;
 use std::io;
 use std::path::{Component, Path, PathBuf};
@@ -21,11 +22,12 @@ impl<W: Write> Archive<W> {
         }
     }
 
-    fn directory(&mut self, path: &Path) -> io::Result<()> {
+    fn directory(&mut self, path: &Path, chmod: u32, owner: Option<&Owner>) -> io::Result<()> {
         let mut header = TarHeader::new_gnu();
         header.set_mtime(self.time);
         header.set_size(0);
-        header.set_mode(0o755);
+        header.set_mode(chmod);
+        set_owner(&mut header, owner)?;
         // Lintian insists on dir paths ending with /, which Rust doesn't
         let mut path_str = path.to_string_lossy().to_string();
         if !path_str.ends_with('/') {
@@ -37,11 +39,16 @@ impl<W: Write> Archive<W> {
     }
 
     fn add_parent_directories(&mut self, path: &Path) -> CDResult<()> {
-        // Append each of the directories found in the file's pathname to the archive before adding the file
-        // For each directory pathname found, attempt to add it to the list of directories
-        let asset_relative_dir = Path::new(".").join(path.parent().ok_or("invalid asset")?);
+        self.add_directories(path.parent().ok_or("invalid asset")?, None)
+    }
+
+    /// Append each of the directories found in the pathname to the archive,
+    /// unless it has been added already. The last one gets the `explicit` mode and owner, if set.
+    fn add_directories(&mut self, path: &Path, explicit: Option<(u32, Option<&Owner>)>) -> CDResult<()> {
+        let asset_relative_dir = Path::new(".").join(path);
         let mut directory = PathBuf::new();
-        for comp in asset_relative_dir.components() {
+        let mut components = asset_relative_dir.components().peekable();
+        while let Some(comp) = components.next() {
             match comp {
                 Component::CurDir if !crate::TAR_REJECTS_CUR_DIR => directory.push("."),
                 Component::Normal(c) => directory.push(c),
@@ -49,29 +56,42 @@ impl<W: Write> Archive<W> {
             }
             if !self.added_directories.contains(&directory) {
                 self.added_directories.insert(directory.clone());
-                self.directory(&directory)?;
+                let (chmod, owner) = explicit.filter(|_| components.peek().is_none()).unwrap_or((0o755, None));
+                self.directory(&directory, chmod, owner)?;
             }
         }
         Ok(())
     }
 
+    /// Directories are otherwise implied by paths of files, with a 0755 mode.
+    /// This must be called before adding any files in this directory, so that the mode is not overridden by the defaults.
+    pub fn explicit_directory(&mut self, path: &Path, chmod: u32, owner: Option<&Owner>) -> CDResult<()> {
+        self.add_directories(path, Some((chmod, owner)))
+    }
+
     pub fn file<P: AsRef<Path>>(&mut self, path: P, out_data: &[u8], chmod: u32) -> CDResult<()> {
-        self.file_(path.as_ref(), out_data, chmod)
+        self.file_(path.as_ref(), out_data.len() as u64, &mut &out_data[..], chmod, None)
     }
 
-    fn file_(&mut self, path: &Path, out_data: &[u8], chmod: u32) -> CDResult<()> {
+    /// Copies `size` bytes from the reader, without buffering the whole file
+    pub fn file_from_reader<P: AsRef<Path>>(&mut self, path: P, size: u64, reader: &mut dyn Read, chmod: u32, owner: Option<&Owner>) -> CDResult<()> {
+        self.file_(path.as_ref(), size, reader, chmod, owner)
+    }
+
+    fn file_(&mut self, path: &Path, size: u64, reader: &mut dyn Read, chmod: u32, owner: Option<&Owner>) -> CDResult<()> {
         self.add_parent_directories(path)?;
 
         let mut header = TarHeader::new_gnu();
         header.set_mtime(self.time);
         header.set_mode(chmod);
-        header.set_size(out_data.len() as u64);
+        set_owner(&mut header, owner)?;
+        header.set_size(size);
         header.set_cksum();
-        self.tar.append_data(&mut header, path, out_data)?;
+        self.tar.append_data(&mut header, path, reader)?;
         Ok(())
     }
 
-    pub fn symlink(&mut self, path: &Path, link_name: &Path) -> CDResult<()> {
+    pub fn symlink(&mut self, path: &Path, link_name: &Path, owner: Option<&Owner>) -> CDResult<()> {
         self.add_parent_directories(path.as_ref())?;
 
         let mut header = TarHeader::new_gnu();
@@ -79,6 +99,7 @@ impl<W: Write> Archive<W> {
         header.set_entry_type(EntryType::Symlink);
         header.set_size(0);
         header.set_mode(0o777);
+        set_owner(&mut header, owner)?;
         header.set_cksum();
         self.tar.append_link(&mut header, path, link_name)?;
         Ok(())
@@ -88,3 +109,24 @@ impl<W: Write> Archive<W> {
         self.tar.into_inner()
     }
 }
+
+/// Numeric IDs are stored as-is. Names are stored as uname/gname,
+/// which dpkg looks up at unpack time, with the IDs left as 0.
+fn set_owner(header: &mut TarHeader, owner: Option<&Owner>) -> io::Result<()> {
+    let (user, group) = owner.map_or(("root", "root"), |o| (o.user.as_str(), o.group.as_str()));
+    match owner.and_then(Owner::uid) {
+        Some(uid) => header.set_uid(uid),
+        None => {
+            header.set_uid(0);
+            header.set_username(user)?;
+        },
+    }
+    match owner.and_then(Owner::gid) {
+        Some(gid) => header.set_gid(gid),
+        None => {
+            header.set_gid(0);
+            header.set_groupname(group)?;
+        },
+    }
+    Ok(())
+}
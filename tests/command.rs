@@ -97,6 +97,13 @@ fn run_cargo_deb_command_on_example_dir() {
     assert!(ddir.path().join("usr/share/doc/example/copyright").exists());
     assert!(ddir.path().join("usr/share/doc/example/changelog.Debian.gz").exists());
     assert!(ddir.path().join("usr/bin/example").exists());
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = |path: &str| fs::metadata(ddir.path().join(path)).unwrap().permissions().mode() & 0o7777;
+        assert_eq!(0o750, mode("var/log/example"));
+        assert_eq!(0o700, mode("var/lib/example"));
+        assert_eq!(0o755, mode("var/lib"));
+    }
     // changelog.Debian.gz starts with the gzip magic
     assert_eq!(
         &[0x1F, 0x8B],
@@ -112,7 +119,7 @@ fn run_cargo_deb_command_on_example_dir_with_variant() {
     assert!(cmd_path.exists());
     let cargo_dir = tempfile::tempdir().unwrap();
     let deb_path = cargo_dir.path().join("test.deb");
-    let output = Command::new(cmd_path)
+    let output = Command::new(&cmd_path)
         .env("CARGO_TARGET_DIR", cargo_dir.path()) // otherwise tests overwrite each other
         .arg("--variant=debug")
         .arg("--no-strip")
@@ -133,6 +140,13 @@ fn run_cargo_deb_command_on_example_dir_with_variant() {
     assert_eq!(printed_deb_path, deb_path);
     assert!(deb_path.exists());
 
+    let inspect = Command::new(&cmd_path).arg("deb").arg("inspect").arg("--json").arg(&deb_path).output().unwrap();
+    assert!(inspect.status.success());
+    let inspect: serde_json::Value = serde_json::from_slice(&inspect.stdout).unwrap();
+    assert_eq!("example-debug", inspect["control"]["Package"]);
+    let files = inspect["files"].as_array().unwrap();
+    assert!(files.iter().any(|f| f["path"] == "var/lib/example/4.txt" && f["md5"] == "835a3c46f2330925774ebf780aa74241" && f["mode"] == "0644"));
+
     let ardir = tempfile::tempdir().unwrap();
     assert!(ardir.path().exists());
     assert!(Command::new("ar")
@@ -261,3 +275,55 @@ fn run_cargo_deb_command_on_example_dir_with_version() {
         &fs::read(ddir.path().join("usr/share/doc/example/changelog.Debian.gz")).unwrap()[..2]
     );
 }
+
+#[test]
+#[cfg(target_os = "linux")]
+fn reproducible_with_source_date_epoch() {
+    let root = PathBuf::from(env::var_os("CARGO_MANIFEST_DIR").unwrap());
+    let cmd_path = root.join(env!("CARGO_BIN_EXE_cargo-deb"));
+    let cargo_dir = tempfile::tempdir().unwrap();
+    let build = |deb_name: &str| {
+        let deb_path = cargo_dir.path().join(deb_name);
+        let output = Command::new(&cmd_path)
+            .env("CARGO_TARGET_DIR", cargo_dir.path()) // otherwise tests overwrite each other
+            .env("SOURCE_DATE_EPOCH", "1600000000")
+            .arg(format!("--output={}", deb_path.display()))
+            .arg(format!("--manifest-path={}", root.join("example/Cargo.toml").display()))
+            .output().unwrap();
+        if !output.status.success() {
+            panic!("Cmd failed: {}\n{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+        }
+        fs::read(deb_path).unwrap()
+    };
+    let first = build("first.deb");
+    let second = build("second.deb");
+    assert!(first == second, "packages built with the same SOURCE_DATE_EPOCH differ");
+}
+
+#[test]
+#[cfg(all(feature = "lzma", target_os = "linux"))]
+fn timestamp_overrides_source_date_epoch() {
+    let root = PathBuf::from(env::var_os("CARGO_MANIFEST_DIR").unwrap());
+    let cmd_path = root.join(env!("CARGO_BIN_EXE_cargo-deb"));
+    let cargo_dir = tempfile::tempdir().unwrap();
+    let deb_path = cargo_dir.path().join("test.deb");
+    let output = Command::new(&cmd_path)
+        .env("CARGO_TARGET_DIR", cargo_dir.path()) // otherwise tests overwrite each other
+        .env("SOURCE_DATE_EPOCH", "not a number")
+        .arg("--timestamp=1234567890")
+        .arg(format!("--output={}", deb_path.display()))
+        .arg(format!("--manifest-path={}", root.join("example/Cargo.toml").display()))
+        .output().unwrap();
+    if !output.status.success() {
+        panic!("Cmd failed: {}\n{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+    }
+
+    let ardir = tempfile::tempdir().unwrap();
+    assert!(Command::new("ar").current_dir(ardir.path()).arg("-x").arg(&deb_path).status().unwrap().success());
+    let ddir = tempfile::tempdir().unwrap();
+    assert!(Command::new("tar").arg("xJf").current_dir(ddir.path()).arg(ardir.path().join("data.tar.xz")).status().unwrap().success());
+
+    let changelog = fs::read(ddir.path().join("usr/share/doc/example/changelog.Debian.gz")).unwrap();
+    // MTIME field of the gzip header
+    assert_eq!(1234567890u32.to_le_bytes(), changelog[4..8]);
+}